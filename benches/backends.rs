@@ -0,0 +1,63 @@
+//! Run time of the recursive sample on the tree walking interpreter and the bytecode vm,
+//! e.g. `cargo bench`. The sample is read and compiled once,
+//! only running its entry function is timed.
+
+use std::time::{Duration, Instant};
+
+use solar_interpreter::{
+    bytecode::Vm,
+    compilation::{CompilerContext, InterpreterContext},
+    project::{read_all_projects, read_modules, read_sources},
+};
+
+const ITERATIONS: u32 = 20;
+
+fn main() {
+    // the std library shipped with the repository
+    std::env::set_var(
+        "SOLAR_PATH",
+        concat!(env!("CARGO_MANIFEST_DIR"), "/solarpath/"),
+    );
+
+    let sample = concat!(env!("CARGO_MANIFEST_DIR"), "/samples/5-recursion");
+    let projects = read_all_projects(sample).expect("read sample");
+    let sources = read_sources(&projects).expect("read sources of sample");
+    let modules = read_modules(&projects, &sources).expect("parse sample");
+    let ctx = CompilerContext::with_default_io(&projects, modules);
+
+    let main = ctx.find_target_function("main").expect("find main");
+    let (main, _) = ctx.compile_symbol(main, &[]).expect("compile sample");
+    let program = ctx.bytecode();
+    let mut io = InterpreterContext::default();
+
+    let (tree, expected) = time(|| ctx.run(main, Vec::new()).expect("run tree walking"));
+    let (bytecode, value) = time(|| {
+        Vm::new(&program, &mut io)
+            .call(main, Vec::new())
+            .expect("run bytecode")
+    });
+
+    // values don't implement equality, but their representation is unique enough
+    assert_eq!(expected.to_string(), value.to_string(), "backends disagree");
+
+    println!("tree walking interpreter: {tree:?} per run");
+    println!("bytecode vm:              {bytecode:?} per run");
+    println!(
+        "speedup: {:.2}x",
+        tree.as_secs_f64() / bytecode.as_secs_f64().max(f64::EPSILON)
+    );
+}
+
+/// Average run time of the closure, along with the value it returned last.
+fn time<T>(mut run: impl FnMut() -> T) -> (Duration, T) {
+    let mut total = Duration::ZERO;
+    let mut value = None;
+
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        value = Some(std::hint::black_box(run()));
+        total += start.elapsed();
+    }
+
+    (total / ITERATIONS, value.expect("at least one iteration"))
+}
@@ -0,0 +1,23 @@
+use @std.types.int.(Int, Bool, add, sub, lessThan, equals)
+
+fun main() =
+    if isEven (fib 10)
+    then fib 20
+    else fib 10
+
+# the return type is inferred from the base case
+fun fib(n: Int) =
+    if lessThan n 2
+    then n
+    else add (fib (sub n 1)) (fib (sub n 2))
+
+# mutual recursion requires declared return types
+fun isEven(n: Int) -> Bool =
+    if equals n 0
+    then true
+    else isOdd (sub n 1)
+
+fun isOdd(n: Int) -> Bool =
+    if equals n 0
+    then false
+    else isEven (sub n 1)
@@ -0,0 +1,10 @@
+use @std.types.int.(Int, add)
+
+type Point
+  - x: Int
+  - y: Int
+
+fun main() =
+    let p = Point 3 4
+    in
+        add (x p) (y p)
@@ -0,0 +1,17 @@
+use @std.types.concat
+use @std.types.String
+use @std.io.println
+
+type Name
+  = Known String
+  | Anonymous
+
+fun main() =
+    let name = Known "Ada"
+    in
+        println (greeting name)
+
+fun greeting(name: Name) -> String =
+    match name
+      | Known n => "Hello " ++ n
+      | Anonymous => "Hello stranger"
@@ -0,0 +1,21 @@
+use @std.types.concat
+use @std.types.String
+use @std.io.println
+
+type Maybe a
+  = Some a
+  | None
+
+type Pair a b
+  - first: a
+  - second: b
+
+fun main() =
+    let pair = Pair (Some "Ada") 36
+    in
+        println (orElse (first pair) "stranger")
+
+fun orElse(m: Maybe a, default: a) -> a =
+    match m
+      | Some value => value
+      | None => default
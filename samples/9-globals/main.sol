@@ -0,0 +1,12 @@
+use @std.types.concat
+use @std.types.int.add
+use @std.io..
+
+let greeting = "Hello " ++ name
+
+let name = "World"
+
+let answer = add 40 2
+
+fun main() =
+    println greeting
@@ -0,0 +1,10 @@
+
+fun add(a: Int, b: Int) -> Int = buildin_int_add a b
+
+fun sub(a: Int, b: Int) -> Int = buildin_int_sub a b
+
+fun mul(a: Int, b: Int) -> Int = buildin_int_mul a b
+
+fun lessThan(a: Int, b: Int) -> Bool = buildin_int_lt a b
+
+fun equals(a: Int, b: Int) -> Bool = buildin_int_eq a b
@@ -3,3 +3,7 @@ buildin_type String
 
 fun concat(a: String, b: String) -> String =
   buildin_str_concat a b
+
+# Number of characters in the string
+fun length(s: String) -> Int =
+  buildin_str_length s
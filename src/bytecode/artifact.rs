@@ -0,0 +1,322 @@
+//! Compiled programs, as written by `solar build`.
+//!
+//! An artifact contains everything needed to run the program,
+//! so neither the sources, nor the libraries in the solar path are needed anymore.
+//!
+//! Layout of the file:
+//!     MAGIC (4 bytes) | FORMAT_VERSION (u32, little endian) | bincode encoded [`Artifact`]
+//!
+//! The format version is increased, whenever the layout of the bytecode changes.
+//! Artifacts of other versions are rejected, they need to be built again.
+//! The bytecode of artifacts is validated after reading, as the file may have been corrupted.
+
+use std::{fs, io::Write};
+
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::Program;
+use crate::{
+    compilation::CompilerContext,
+    id::{FunctionId, SSID},
+    types::Type,
+};
+
+pub const MAGIC: &[u8; 4] = b"SOLC";
+pub const FORMAT_VERSION: u32 = 1;
+
+/// File extension of artifacts
+pub const EXTENSION: &str = "solc";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Artifact {
+    /// Name of the project, the artifact was built from
+    pub name: String,
+    /// Function, the program starts with
+    pub entry: FunctionId,
+    pub program: Program,
+    /// All types, indexed by their TypeId
+    pub types: Vec<(SSID, Type)>,
+}
+
+#[derive(Debug, Error)]
+pub enum ArtifactError {
+    /// The file doesn't start with the magic bytes
+    NotAnArtifact(String),
+    /// The artifact has been built by another version of solar
+    Version {
+        path: String,
+        found: u32,
+    },
+    /// The bytecode would make the vm fail, e.g. by referring to a missing constant
+    Malformed {
+        path: String,
+        problem: String,
+    },
+    /// The entry function takes a different number of arguments
+    Arguments {
+        expected: usize,
+        got: usize,
+    },
+    Encoding(#[from] bincode::Error),
+    Io(#[from] std::io::Error),
+}
+
+impl std::fmt::Display for ArtifactError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotAnArtifact(path) => write!(f, "{path} is not a compiled solar program"),
+            Self::Version { path, found } => write!(
+                f,
+                "{path} has format version {found}, but this version of solar reads format version {FORMAT_VERSION}. \
+                Build it again with `solar build`"
+            ),
+            Self::Malformed { path, problem } => {
+                write!(f, "{path} is not a valid compiled solar program: {problem}")
+            }
+            Self::Arguments { expected, got } => write!(
+                f,
+                "the entry function takes {expected} arguments, but {got} were supplied"
+            ),
+            Self::Encoding(e) => write!(f, "invalid compiled solar program: {e}"),
+            Self::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Artifact {
+    /// Collects all compiled functions, types and globals of the context.
+    /// The entry function needs to be compiled already.
+    pub fn build(ctx: &CompilerContext, name: &str, entry: FunctionId) -> Self {
+        let types = ctx.types.read().expect("aquire readlock for types");
+
+        let mut types = types
+            .iter()
+            .map(|(ssid, id, ty)| (id, ssid.clone(), ty.clone()))
+            .collect::<Vec<_>>();
+        types.sort_by_key(|(id, _, _)| *id);
+
+        Artifact {
+            name: name.to_string(),
+            entry,
+            program: ctx.bytecode(),
+            types: types.into_iter().map(|(_, ssid, ty)| (ssid, ty)).collect(),
+        }
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), ArtifactError> {
+        let mut file = fs::File::create(path)?;
+        file.write_all(MAGIC)?;
+        file.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut file, self)?;
+
+        Ok(())
+    }
+
+    pub fn read(path: &str) -> Result<Self, ArtifactError> {
+        let content = fs::read(path)?;
+
+        let Some(content) = content.strip_prefix(MAGIC) else {
+            return Err(ArtifactError::NotAnArtifact(path.to_string()));
+        };
+
+        let Some((version, content)) = content.split_first_chunk::<4>() else {
+            return Err(ArtifactError::NotAnArtifact(path.to_string()));
+        };
+
+        let found = u32::from_le_bytes(*version);
+        if found != FORMAT_VERSION {
+            return Err(ArtifactError::Version {
+                path: path.to_string(),
+                found,
+            });
+        }
+
+        // Same encoding as `bincode::serialize`, but corrupted lengths
+        // can't make it allocate more memory, than the file is large.
+        let artifact: Artifact = bincode::options()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .with_limit(content.len() as u64)
+            .deserialize(content)?;
+
+        let malformed = |problem| ArtifactError::Malformed {
+            path: path.to_string(),
+            problem,
+        };
+        artifact.program.validate().map_err(malformed)?;
+        if artifact.program.chunk(artifact.entry).is_none() {
+            let problem = format!("entry function #{} has no bytecode", artifact.entry);
+            return Err(malformed(problem));
+        }
+
+        Ok(artifact)
+    }
+
+    /// Checks, that the entry function can be called with the number of arguments.
+    pub fn check_arguments(&self, args: usize) -> Result<(), ArtifactError> {
+        let expected = self
+            .program
+            .chunk(self.entry)
+            .map(|chunk| chunk.args as usize)
+            .unwrap_or_default();
+
+        if expected != args {
+            return Err(ArtifactError::Arguments {
+                expected,
+                got: args,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bytecode::{Chunk, Op},
+        value::{Int, Value},
+    };
+
+    fn artifact(code: Vec<Op>) -> Artifact {
+        let chunk = Chunk {
+            code,
+            constants: vec![Value::Int(Int::Int64(42))],
+            args: 0,
+            locals: 0,
+        };
+
+        Artifact {
+            name: "answer".to_string(),
+            entry: 0,
+            program: Program {
+                functions: vec![Some(chunk)],
+                globals: Vec::new(),
+            },
+            types: Vec::new(),
+        }
+    }
+
+    fn path(test: &str) -> String {
+        let path = std::env::temp_dir().join(format!("solar-{}-{test}.solc", std::process::id()));
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = path("round-trip");
+        artifact(vec![Op::Const(0), Op::Return])
+            .write(&path)
+            .unwrap();
+
+        let artifact = Artifact::read(&path).unwrap();
+        assert_eq!(artifact.name, "answer");
+        assert_eq!(artifact.entry, 0);
+        assert_eq!(
+            format!("{:?}", artifact.program.chunk(0).unwrap().code),
+            "[Const(0), Return]"
+        );
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_other_format_versions() {
+        let path = path("version");
+        artifact(vec![Op::Const(0), Op::Return])
+            .write(&path)
+            .unwrap();
+
+        let mut content = fs::read(&path).unwrap();
+        content[4..8].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        fs::write(&path, content).unwrap();
+
+        let result = Artifact::read(&path);
+        assert!(
+            matches!(result, Err(ArtifactError::Version { found, .. }) if found == FORMAT_VERSION + 1)
+        );
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let path = path("not-an-artifact");
+        fs::write(&path, "fun main() = 42").unwrap();
+
+        let result = Artifact::read(&path);
+        assert!(matches!(result, Err(ArtifactError::NotAnArtifact(_))));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn limits_lengths_to_the_size_of_the_file() {
+        let path = path("length");
+        let mut content = MAGIC.to_vec();
+        content.extend(FORMAT_VERSION.to_le_bytes());
+        // length of the name
+        content.extend(u64::MAX.to_le_bytes());
+        fs::write(&path, content).unwrap();
+
+        let result = Artifact::read(&path);
+        assert!(matches!(result, Err(ArtifactError::Encoding(_))));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_malformed_bytecode() {
+        let malformed = [
+            // missing constant
+            vec![Op::Const(1), Op::Return],
+            // missing slot
+            vec![Op::GetLocal(0), Op::Return],
+            // popping from an empty stack
+            vec![Op::Pop, Op::Const(0), Op::Return],
+            // running past the end of the code
+            vec![Op::Const(0)],
+            // jumping outside of the code
+            vec![Op::Const(0), Op::Jump(7)],
+            // one of the branches pops too much
+            vec![
+                Op::Const(0),
+                Op::JumpIfFalse(4),
+                Op::Const(0),
+                Op::Return,
+                Op::Return,
+            ],
+            // calling a function with the wrong number of arguments
+            vec![Op::Const(0), Op::Call { func: 0, args: 1 }, Op::Return],
+        ];
+
+        for (i, code) in malformed.into_iter().enumerate() {
+            let path = path(&format!("malformed-{i}"));
+            artifact(code).write(&path).unwrap();
+
+            let result = Artifact::read(&path);
+            assert!(
+                matches!(result, Err(ArtifactError::Malformed { .. })),
+                "case {i}: {result:?}"
+            );
+
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn requires_bytecode_of_the_entry_function() {
+        let path = path("entry");
+        let mut artifact = artifact(vec![Op::Const(0), Op::Return]);
+        artifact.entry = 1;
+        artifact.write(&path).unwrap();
+
+        let result = Artifact::read(&path);
+        assert!(matches!(result, Err(ArtifactError::Malformed { .. })));
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,686 @@
+//! Cache of compiled functions inside `.solar-cache/` of the target project.
+//!
+//! The bytecode of every compiled function is stored in the file of the module, it's declared in.
+//! [`FunctionId`]s and [`TypeId`]s depend on the order of compilation,
+//! so the cached bytecode refers to functions and types by their [`StableId`] instead,
+//! and to globals by their [`SymbolId`].
+//! Once it's loaded, the bytecode is linked to the ids of the current compilation again,
+//! see [`CompilerContext::reserve_cached`].
+//!
+//! A function depends on the modules of all functions, types and globals it refers to,
+//! as well as on all modules, these import from, directly or indirectly.
+//! Each module is identified by the hash of its files and of the dependencies of its project.
+//! A function is only loaded, if none of the modules it depends on have changed since,
+//! so editing a file only invalidates the functions depending on it.
+//! All other functions are compiled from source.
+//!
+//! Globals are evaluated again in every compilation, their values aren't cached.
+//! They can't perform IO (see [`crate::compilation::CompilationError::ImpureGlobal`]),
+//! so values folded into the bytecode only depend on the modules, the function depends on.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{Chunk, Op, Program};
+use crate::{
+    compilation::{CachedSlot, CompilerContext},
+    id::{FunctionId, GlobalId, IdItem, IdModule, StableId, SymbolId, TypeId},
+    value::Value,
+};
+
+pub const CACHE_DIR: &str = ".solar-cache";
+
+/// File extension of the cached modules
+const EXTENSION: &str = "cache";
+
+/// Cached functions, that have been declared in the same module.
+#[derive(Debug, Serialize, Deserialize)]
+struct ModuleCache {
+    /// Version of solar, that compiled the functions
+    compiler: String,
+    module: IdModule,
+    /// Hashes of all modules, the functions depend on, at the time they were compiled
+    digests: BTreeMap<IdModule, String>,
+    functions: Vec<CachedFunction>,
+}
+
+/// Bytecode of a function, whose calls, types and globals
+/// refer to the tables of the function by index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedFunction {
+    id: StableId,
+    return_type: StableId,
+    /// Modules, that must not have changed for the bytecode to be valid
+    dependencies: BTreeSet<IdModule>,
+    chunk: Chunk,
+    functions: Vec<StableId>,
+    types: Vec<StableId>,
+    globals: Vec<SymbolId>,
+}
+
+/// Cached functions of the target project
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Cache of the target project at `fsroot`.
+    pub fn new(fsroot: &str) -> Self {
+        // scripts store their cache next to them
+        let root = Path::new(fsroot);
+        let root = if root.is_file() {
+            root.parent().unwrap_or(Path::new(""))
+        } else {
+            root
+        };
+
+        Cache {
+            dir: root.join(CACHE_DIR).join("modules"),
+        }
+    }
+
+    /// Loads all cached functions, whose modules haven't changed since they have been cached.
+    /// Functions, that can't be linked, are compiled from source instead.
+    /// Unreadable files are treated as missing.
+    /// Returns the number of functions loaded.
+    pub fn load<'a>(&self, ctx: &'a CompilerContext<'a>) -> usize {
+        let digests = digests(ctx);
+
+        // sorted, so that the functions get the same ids every time
+        let mut modules = ctx.module_info.keys().collect::<Vec<_>>();
+        modules.sort();
+
+        let cached = modules
+            .into_iter()
+            .filter_map(|module| self.read(module))
+            .flat_map(|cache| cache.valid_functions(&digests))
+            .collect::<Vec<_>>();
+
+        // All functions are reserved first, so that they can be linked to each other.
+        let reserved = cached
+            .into_iter()
+            .filter_map(|function| {
+                let slot = ctx.reserve_cached(&function.id, &function.return_type)?;
+                Some((slot, function))
+            })
+            .collect::<Vec<_>>();
+
+        let mut discarded = Vec::new();
+        let mut pending = Vec::new();
+        for (slot, function) in reserved {
+            match function.link(ctx, &slot) {
+                Some(chunk) => pending.push((slot, chunk, function.globals)),
+                None => discarded.push(slot),
+            }
+        }
+
+        // Globals are linked last, because evaluating them may call the cached functions.
+        // Those are only available, once they have been completed.
+        let mut loaded = 0;
+        loop {
+            let mut waiting = Vec::new();
+            let before = pending.len();
+
+            for (slot, chunk, globals) in pending {
+                match link_globals(ctx, &chunk, &globals) {
+                    Some(chunk) => {
+                        ctx.complete_cached(slot, chunk);
+                        loaded += 1;
+                    }
+                    None => waiting.push((slot, chunk, globals)),
+                }
+            }
+
+            pending = waiting;
+            if pending.is_empty() || pending.len() == before {
+                break;
+            }
+        }
+
+        discarded.extend(pending.into_iter().map(|(slot, _, _)| slot));
+        ctx.discard_cached(discarded);
+
+        loaded
+    }
+
+    /// Stores the bytecode of all compiled functions, as translated by [`CompilerContext::bytecode`].
+    /// Functions cached by earlier compilations are kept, as long as they are still valid,
+    /// so functions, that this program doesn't need, stay cached as well.
+    pub fn store(&self, ctx: &CompilerContext, program: &Program) -> anyhow::Result<()> {
+        let digests = digests(ctx);
+        let mut imports = Imports::new(ctx);
+
+        let functions = ctx.functions.read().expect("aquire readlock for functions");
+        let ssids = functions
+            .iter()
+            .map(|(ssid, id, _)| (id, ctx.stable_id(ssid)))
+            .collect::<HashMap<_, _>>();
+        let globals = ctx
+            .globals
+            .read()
+            .expect("aquire readlock for globals")
+            .iter()
+            .map(|(symbol, id, _)| (id, symbol.clone()))
+            .collect::<HashMap<_, _>>();
+
+        let mut modules: BTreeMap<&IdModule, Vec<CachedFunction>> = BTreeMap::new();
+        for (ssid, id, _) in functions.iter() {
+            // tests are compiled by the test runner only
+            if matches!(ssid.0 .2, IdItem::Test(_)) {
+                continue;
+            }
+
+            let (Some(chunk), Some(return_type)) = (program.chunk(id), functions.return_type(id))
+            else {
+                continue;
+            };
+
+            let function = CachedFunction::unlink(
+                ctx,
+                ssids[&id].clone(),
+                ctx.stable_type(return_type),
+                chunk,
+                &ssids,
+                &globals,
+                &mut imports,
+            );
+            modules.entry(&ssid.0 .0).or_default().push(function);
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        for (module, mut functions) in modules {
+            let compiled = functions
+                .iter()
+                .map(|function| function.id.clone())
+                .collect::<BTreeSet<_>>();
+
+            let mut kept = self
+                .read(module)
+                .map(|cache| cache.valid_functions(&digests))
+                .unwrap_or_default();
+            kept.retain(|function| !compiled.contains(&function.id));
+
+            functions.extend(kept);
+            functions.sort_by(|a, b| a.id.cmp(&b.id));
+
+            let dependencies = functions
+                .iter()
+                .flat_map(|function| &function.dependencies)
+                .filter_map(|dependency| {
+                    Some((dependency.clone(), digests.get(dependency)?.clone()))
+                })
+                .collect();
+
+            let cache = ModuleCache {
+                compiler: env!("CARGO_PKG_VERSION").to_string(),
+                module: module.clone(),
+                digests: dependencies,
+                functions,
+            };
+            self.write(&cache)?;
+        }
+
+        Ok(())
+    }
+
+    fn path(&self, module: &IdModule) -> PathBuf {
+        let digest = hash_content(module.join("/").as_bytes());
+        let name = digest.trim_start_matches("sha256:");
+
+        self.dir.join(format!("{}.{EXTENSION}", &name[..16]))
+    }
+
+    fn read(&self, module: &IdModule) -> Option<ModuleCache> {
+        let content = fs::read(self.path(module)).ok()?;
+
+        // Same encoding as `bincode::serialize`, but corrupted lengths
+        // can't make it allocate more memory, than the file is large.
+        let cache: ModuleCache = bincode::options()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .with_limit(content.len() as u64)
+            .deserialize(&content)
+            .ok()?;
+
+        let current = cache.compiler == env!("CARGO_PKG_VERSION") && cache.module == *module;
+        current.then_some(cache)
+    }
+
+    fn write(&self, cache: &ModuleCache) -> anyhow::Result<()> {
+        let path = self.path(&cache.module);
+        let content = bincode::serialize(cache)?;
+
+        if fs::read(&path).is_ok_and(|existing| existing == content) {
+            return Ok(());
+        }
+
+        // written to a temporary file first, so that an interrupted write doesn't leave a truncated file.
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, content)?;
+        fs::rename(tmp, path)?;
+
+        Ok(())
+    }
+}
+
+impl ModuleCache {
+    /// Functions, whose dependencies have the same hashes as in the current compilation.
+    fn valid_functions(self, digests: &HashMap<IdModule, String>) -> Vec<CachedFunction> {
+        let cached = self.digests;
+        let unchanged = |module: &IdModule| {
+            cached
+                .get(module)
+                .is_some_and(|hash| digests.get(module) == Some(hash))
+        };
+
+        self.functions
+            .into_iter()
+            .filter(|function| function.dependencies.iter().all(unchanged))
+            .collect()
+    }
+}
+
+impl CachedFunction {
+    /// Replaces the ids of the compiled bytecode by indices into the tables of the function.
+    fn unlink(
+        ctx: &CompilerContext,
+        id: StableId,
+        return_type: StableId,
+        chunk: &Chunk,
+        ssids: &HashMap<FunctionId, StableId>,
+        globals: &HashMap<GlobalId, SymbolId>,
+        imports: &mut Imports,
+    ) -> Self {
+        let mut functions = Vec::new();
+        let mut types = Vec::new();
+        let mut global_symbols = Vec::new();
+
+        let mut chunk = chunk.clone();
+        relink(
+            &mut chunk,
+            &mut |func, _| Some(intern(&mut functions, ssids[&func].clone())),
+            &mut |ty| Some(intern(&mut types, ctx.stable_type(ty))),
+            &mut |global| Some(intern(&mut global_symbols, globals[&global].clone())),
+        )
+        .expect("ids of the compilation to be known");
+
+        let mut modules = vec![&id.symbol.0];
+        modules.extend(return_type.modules());
+        modules.extend(functions.iter().flat_map(StableId::modules));
+        modules.extend(types.iter().flat_map(StableId::modules));
+        modules.extend(global_symbols.iter().map(|(module, _, _)| module));
+
+        let dependencies = imports.closure(modules);
+
+        CachedFunction {
+            id,
+            return_type,
+            dependencies,
+            chunk,
+            functions,
+            types,
+            globals: global_symbols,
+        }
+    }
+
+    /// Links the bytecode to the functions and types of the compilation.
+    /// Globals are still referred to by their index into the table, see [`link_globals`].
+    fn link<'a>(&self, ctx: &'a CompilerContext<'a>, slot: &CachedSlot) -> Option<Chunk> {
+        if self.chunk.args as usize != slot.args {
+            return None;
+        }
+
+        let functions = self
+            .functions
+            .iter()
+            .map(|id| ctx.cached_function(id))
+            .collect::<Option<Vec<_>>>()?;
+        let types = self
+            .types
+            .iter()
+            .map(|id| ctx.cached_type(id))
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut chunk = self.chunk.clone();
+        relink(
+            &mut chunk,
+            &mut |func, args| match functions.get(func) {
+                Some((func, expected)) if *expected == args as usize => Some(*func),
+                _ => None,
+            },
+            &mut |ty| types.get(ty).copied(),
+            &mut |global| (global < self.globals.len()).then_some(global),
+        )?;
+
+        // the cache may have been corrupted
+        chunk.validate(&Program::default()).ok()?;
+
+        Some(chunk)
+    }
+}
+
+/// Links the globals of the bytecode to the globals of the compilation,
+/// evaluating them, unless they have been evaluated before.
+fn link_globals<'a>(
+    ctx: &'a CompilerContext<'a>,
+    chunk: &Chunk,
+    symbols: &[SymbolId],
+) -> Option<Chunk> {
+    let globals = symbols
+        .iter()
+        .map(|symbol| ctx.cached_global(symbol))
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut chunk = chunk.clone();
+    relink(
+        &mut chunk,
+        &mut |func, _| Some(func),
+        &mut Some,
+        &mut |global| globals.get(global).copied(),
+    )?;
+
+    Some(chunk)
+}
+
+/// Replaces the ids of all functions, types and globals, the bytecode refers to.
+/// Fails, if any of them can't be replaced.
+fn relink(
+    chunk: &mut Chunk,
+    function: &mut impl FnMut(FunctionId, u16) -> Option<FunctionId>,
+    ty: &mut impl FnMut(TypeId) -> Option<TypeId>,
+    global: &mut impl FnMut(GlobalId) -> Option<GlobalId>,
+) -> Option<()> {
+    for op in &mut chunk.code {
+        match op {
+            Op::Call { func, args } => *func = function(*func, *args)?,
+            Op::NewStruct { ty: id, .. } | Op::NewVariant { ty: id, .. } => *id = ty(*id)?,
+            Op::GetGlobal(id) => *id = global(*id)?,
+            _ => {}
+        }
+    }
+
+    for constant in &mut chunk.constants {
+        relink_value(constant, ty)?;
+    }
+
+    Some(())
+}
+
+/// Replaces the types of constants, e.g. of struct instances.
+fn relink_value(value: &mut Value, ty: &mut impl FnMut(TypeId) -> Option<TypeId>) -> Option<()> {
+    match value {
+        Value::Struct { ty: id, fields } => {
+            *id = ty(*id)?;
+            for field in fields {
+                relink_value(field, ty)?;
+            }
+        }
+        Value::Enum {
+            ty: id, payload, ..
+        } => {
+            *id = ty(*id)?;
+            if let Some(payload) = payload {
+                relink_value(payload, ty)?;
+            }
+        }
+        _ => {}
+    }
+
+    Some(())
+}
+
+/// Index of the id inside the table, it's appended, unless it's there already.
+fn intern<T: PartialEq>(table: &mut Vec<T>, id: T) -> usize {
+    match table.iter().position(|entry| *entry == id) {
+        Some(index) => index,
+        None => {
+            table.push(id);
+            table.len() - 1
+        }
+    }
+}
+
+/// Modules, that the modules of the compilation import from.
+struct Imports<'c, 'a> {
+    ctx: &'c CompilerContext<'a>,
+    direct: HashMap<IdModule, BTreeSet<IdModule>>,
+}
+
+impl<'c, 'a> Imports<'c, 'a> {
+    fn new(ctx: &'c CompilerContext<'a>) -> Self {
+        Imports {
+            ctx,
+            direct: HashMap::new(),
+        }
+    }
+
+    /// The modules along with all modules, they import from, directly or indirectly.
+    fn closure<'m>(
+        &mut self,
+        modules: impl IntoIterator<Item = &'m IdModule>,
+    ) -> BTreeSet<IdModule> {
+        let mut closure = BTreeSet::new();
+        let mut pending = modules.into_iter().cloned().collect::<Vec<_>>();
+
+        while let Some(module) = pending.pop() {
+            if closure.contains(&module) {
+                continue;
+            }
+
+            pending.extend(self.direct(&module).iter().cloned());
+            closure.insert(module);
+        }
+
+        closure
+    }
+
+    /// Modules, that the files of the module import from.
+    /// Imports of symbols, that aren't modules, are left out.
+    fn direct(&mut self, module: &IdModule) -> &BTreeSet<IdModule> {
+        let ctx = self.ctx;
+
+        self.direct.entry(module.clone()).or_insert_with(|| {
+            let Some(module) = ctx.module_info.get(module) else {
+                return BTreeSet::new();
+            };
+
+            module
+                .files
+                .iter()
+                .flat_map(|file| {
+                    file.imports
+                        .values()
+                        .flatten()
+                        .chain(&file.wildcard_imports)
+                })
+                .filter(|module| ctx.module_info.contains_key(*module))
+                .cloned()
+                .collect()
+        })
+    }
+}
+
+/// Hashes of all modules of the compilation,
+/// covering the names and contents of their files
+/// and the dependencies of their project, which their imports are resolved with.
+fn digests(ctx: &CompilerContext) -> HashMap<IdModule, String> {
+    ctx.module_info
+        .iter()
+        .map(|(id, module)| {
+            let mut content = Vec::new();
+            for file in &module.files {
+                content.extend(file.filename.as_bytes());
+                content.push(0);
+                content.extend(file.content.as_bytes());
+                content.push(0);
+            }
+
+            if let Some(project) = ctx.project_info.get_by_index(module.project_id) {
+                let dependencies = project.dep_map.iter().collect::<BTreeMap<_, _>>();
+                for (name, path) in dependencies {
+                    content.extend(name.as_bytes());
+                    content.push(0);
+                    content.extend(path.join("/").as_bytes());
+                    content.push(0);
+                }
+            }
+
+            (id.clone(), hash_content(&content))
+        })
+        .collect()
+}
+
+fn hash_content(content: &[u8]) -> String {
+    let hash = Sha256::digest(content)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+
+    format!("sha256:{hash}")
+}
+
+#[cfg(test)]
+mod tests {
+    use solar_parser::ast::body::BodyItem;
+
+    use super::*;
+    use crate::{
+        compilation::FunctionInfo,
+        project::{read_all_projects, read_modules, read_sources},
+        util,
+    };
+
+    const UTIL: &str = "use @std.types.int.(Int, add)\n\
+                        \n\
+                        fun double(n: Int) -> Int = add n n\n";
+
+    /// Project in a temporary directory, depending on the std library shipped with the repository.
+    /// It consists of main.sol and the module util, which main.sol may import from.
+    /// The directory is removed, once the project is dropped.
+    struct TempProject(PathBuf);
+
+    impl TempProject {
+        fn new(test: &str, main: &str) -> Self {
+            let root =
+                std::env::temp_dir().join(format!("solar-cache-{}-{test}", std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(root.join("util")).unwrap();
+
+            let std = concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/solarpath/libraries/std(solar-lang)/0.0.1"
+            );
+            let yaml = format!(
+                "name: test\nversion: 0.1.0\ndependencies:\n  std(solar-lang): {{ path: \"{std}\" }}\n"
+            );
+            fs::write(root.join("solar.yaml"), yaml).unwrap();
+            fs::write(root.join("util/util.sol"), UTIL).unwrap();
+
+            let project = TempProject(root);
+            project.write("main.sol", main);
+            project
+        }
+
+        fn write(&self, file: &str, code: &str) {
+            fs::write(self.0.join(file), code).unwrap();
+        }
+
+        fn root(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+    }
+
+    impl Drop for TempProject {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Runs main on the bytecode vm, like `solar run` does.
+    /// Returns the result, along with the functions of the project, that have been loaded from the cache.
+    fn run(project: &TempProject) -> (String, Vec<String>) {
+        let projects = read_all_projects(project.root()).unwrap();
+        let sources = read_sources(&projects).unwrap();
+        let modules = read_modules(&projects, &sources).unwrap();
+        let ctx = CompilerContext::with_default_io(&projects, modules);
+
+        let cache = Cache::new(project.root());
+        cache.load(&ctx);
+
+        let main = ctx.find_target_function("main").unwrap();
+        let (main, _) = ctx.compile_symbol(main, &[]).unwrap();
+        let program = ctx.bytecode();
+        cache.store(&ctx, &program).unwrap();
+
+        let value = ctx.run_program(&program, main, Vec::new()).unwrap();
+
+        let functions = ctx.functions.read().unwrap();
+        let mut cached = functions
+            .iter()
+            .filter(|(ssid, _, info)| {
+                ssid.0 .0.starts_with(&util::target_id())
+                    && matches!(info, FunctionInfo::Cached { .. })
+            })
+            .filter_map(|(ssid, _, _)| match ctx.get_symbol(ssid.0.clone()).2 {
+                BodyItem::Function(func) => Some(func.name.value.to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        cached.sort();
+
+        (ctx.display_value(&value), cached)
+    }
+
+    #[test]
+    fn loads_the_functions_compiled_before() {
+        let project = TempProject::new(
+            "round-trip",
+            "use util.(double)\n\
+             \n\
+             fun main() = double (double 3)\n",
+        );
+
+        assert_eq!(run(&project), ("12".to_string(), Vec::new()));
+        assert_eq!(
+            run(&project),
+            (
+                "12".to_string(),
+                vec!["double".to_string(), "main".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn editing_a_file_invalidates_the_functions_depending_on_it() {
+        let project = TempProject::new(
+            "invalidation",
+            "use util.(double)\n\
+             \n\
+             fun main() = double 3\n",
+        );
+        run(&project);
+
+        // util doesn't import from the root module, so double stays valid.
+        project.write(
+            "main.sol",
+            "use util.(double)\n\
+             \n\
+             fun main() = double 5\n",
+        );
+        assert_eq!(
+            run(&project),
+            ("10".to_string(), vec!["double".to_string()])
+        );
+
+        // main imports from util, so it's compiled again as well.
+        project.write("util/util.sol", &UTIL.replace("add n n", "add n (add n n)"));
+        assert_eq!(run(&project), ("15".to_string(), Vec::new()));
+    }
+}
@@ -0,0 +1,178 @@
+//! Translates the MIR of a function into bytecode
+
+use super::{Address, Chunk, Op};
+use crate::{
+    mir::{Instruction, StaticExpression},
+    value::Value,
+};
+
+pub fn compile_function(args: usize, body: &StaticExpression) -> Chunk {
+    let mut gen = Codegen {
+        code: Vec::new(),
+        constants: Vec::new(),
+        locals: args as u16,
+    };
+
+    gen.expr(body);
+    gen.emit(Op::Return);
+
+    Chunk {
+        code: gen.code,
+        constants: gen.constants,
+        args: args as u16,
+        locals: gen.locals,
+    }
+}
+
+struct Codegen {
+    code: Vec<Op>,
+    constants: Vec<Value>,
+    /// Number of slots needed so far
+    locals: u16,
+}
+
+impl Codegen {
+    fn emit(&mut self, op: Op) -> Address {
+        self.code.push(op);
+        (self.code.len() - 1) as Address
+    }
+
+    fn here(&self) -> Address {
+        self.code.len() as Address
+    }
+
+    /// Points a previously emitted jump at the current position
+    fn patch(&mut self, jump: Address) {
+        let target = self.here();
+        match &mut self.code[jump as usize] {
+            Op::Jump(address) | Op::JumpIfFalse(address) => *address = target,
+            op => unreachable!("patching {op:?}, which is not a jump"),
+        }
+    }
+
+    fn slot(&mut self, index: u16) -> u16 {
+        self.locals = self.locals.max(index + 1);
+        index
+    }
+
+    /// Emits code, that leaves the value of the expression on the stack
+    fn expr(&mut self, expr: &StaticExpression) {
+        match expr.instr.as_ref() {
+            Instruction::Const(value) => {
+                self.constants.push(value.clone());
+                self.emit(Op::Const((self.constants.len() - 1) as u32));
+            }
+            Instruction::GetLocalVar(addr) => {
+                let slot = self.slot(*addr as u16);
+                self.emit(Op::GetLocal(slot));
+            }
+            Instruction::GetGlobalVar(id) => {
+                self.emit(Op::GetGlobal(*id));
+            }
+            Instruction::NewLocalVar {
+                var_index,
+                var_value,
+                body,
+            } => {
+                self.expr(var_value);
+                let slot = self.slot(*var_index);
+                self.emit(Op::SetLocal(slot));
+                self.expr(body);
+            }
+            Instruction::FunctionCall { func, args } => {
+                self.exprs(args);
+                self.emit(Op::Call {
+                    func: *func,
+                    args: args.len() as u16,
+                });
+            }
+            Instruction::Custom { code, args } => {
+                self.exprs(args);
+                self.emit(Op::Custom {
+                    code: *code,
+                    args: args.len() as u16,
+                });
+            }
+            Instruction::IfExpr {
+                condition,
+                case_true,
+                case_false,
+            } => {
+                self.expr(condition);
+                let to_false = self.emit(Op::JumpIfFalse(0));
+                self.expr(case_true);
+                let to_end = self.emit(Op::Jump(0));
+                self.patch(to_false);
+                self.expr(case_false);
+                self.patch(to_end);
+            }
+            Instruction::NewStruct { fields } => {
+                self.exprs(fields);
+                self.emit(Op::NewStruct {
+                    ty: expr.ty,
+                    fields: fields.len() as u16,
+                });
+            }
+            Instruction::GetField { value, field } => {
+                self.expr(value);
+                self.emit(Op::GetField(*field));
+            }
+            Instruction::NewVariant { variant, payload } => {
+                if let Some(payload) = payload {
+                    self.expr(payload);
+                }
+                self.emit(Op::NewVariant {
+                    ty: expr.ty,
+                    variant: *variant,
+                    payload: payload.is_some(),
+                });
+            }
+            Instruction::MatchVariant {
+                value,
+                cases,
+                default,
+            } => {
+                self.expr(value);
+
+                let mut to_end = Vec::new();
+                for case in cases {
+                    self.emit(Op::TestVariant(case.variant));
+                    let to_next = self.emit(Op::JumpIfFalse(0));
+
+                    self.emit(Op::Payload);
+                    match case.binding {
+                        Some(index) => {
+                            let slot = self.slot(index);
+                            self.emit(Op::SetLocal(slot))
+                        }
+                        None => self.emit(Op::Pop),
+                    };
+
+                    self.expr(&case.body);
+                    to_end.push(self.emit(Op::Jump(0)));
+                    self.patch(to_next);
+                }
+
+                match default {
+                    Some(default) => {
+                        self.emit(Op::Pop);
+                        self.expr(default);
+                    }
+                    None => {
+                        self.emit(Op::Unmatched);
+                    }
+                }
+
+                for jump in to_end {
+                    self.patch(jump);
+                }
+            }
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[StaticExpression]) {
+        for expr in exprs {
+            self.expr(expr);
+        }
+    }
+}
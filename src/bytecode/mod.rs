@@ -0,0 +1,252 @@
+//! Flat, stack based bytecode, compiled from the MIR of complete functions.
+//!
+//! Every function is compiled into a [`Chunk`].
+//! Calls are linked already: they refer to the [`FunctionId`] of the callee,
+//! which is the index of its chunk inside the [`Program`].
+//!
+//! All values live on a single stack.
+//! The frame of a call starts with its arguments,
+//! followed by the slots of the let bindings, as they have been numbered by the [`crate::util::Scope`].
+//! Intermediate values are pushed on top of the frame.
+mod artifact;
+mod cache;
+mod codegen;
+mod vm;
+
+pub use artifact::*;
+pub use cache::*;
+pub use vm::Vm;
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compilation::{FunctionInfo, FunctionStore, GlobalStore},
+    id::{FunctionId, GlobalId, TypeId},
+    mir::CustomInstructionCode,
+    value::Value,
+};
+
+/// Position of an instruction inside its chunk
+pub type Address = u32;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Op {
+    /// Pushes a constant of the chunk
+    Const(u32),
+    /// Pushes a copy of a local variable
+    GetLocal(u16),
+    /// Pops a value into a local variable
+    SetLocal(u16),
+    /// Pushes the value of a global variable
+    GetGlobal(GlobalId),
+    /// Pops the arguments and pushes the return value of the function
+    Call {
+        func: FunctionId,
+        args: u16,
+    },
+    /// Pops the arguments and pushes the result of the buildin
+    Custom {
+        code: CustomInstructionCode,
+        args: u16,
+    },
+    Jump(Address),
+    /// Pops a bool and jumps, if it's false
+    JumpIfFalse(Address),
+    /// Discards the topmost value
+    Pop,
+    /// Pops the fields and pushes the struct instance
+    NewStruct {
+        ty: TypeId,
+        fields: u16,
+    },
+    /// Replaces the struct instance on top by one of its fields
+    GetField(u16),
+    /// Pushes a value of an enum, popping the payload, if there is one
+    NewVariant {
+        ty: TypeId,
+        variant: u16,
+        payload: bool,
+    },
+    /// Pushes whether the enum value on top has the variant. The enum value is kept.
+    TestVariant(u16),
+    /// Replaces the enum value on top by its payload, or void
+    Payload,
+    /// Pops the enum value, that none of the cases of a match handle
+    Unmatched,
+    /// Returns the topmost value to the caller
+    Return,
+}
+
+/// Bytecode of a single function
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    pub constants: Vec<Value>,
+    /// Number of arguments
+    pub args: u16,
+    /// Number of slots in the frame, including the arguments
+    pub locals: u16,
+}
+
+/// All compiled functions, indexed by their [`FunctionId`],
+/// along with the values of the global variables, indexed by their [`GlobalId`].
+/// Functions, that haven't been compiled completely, have no chunk.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Program {
+    pub functions: Vec<Option<Chunk>>,
+    pub globals: Vec<Option<Value>>,
+}
+
+impl Program {
+    /// Compiles all complete functions of the store.
+    /// Functions loaded from the cache are bytecode already.
+    pub fn compile(functions: &FunctionStore, globals: &GlobalStore) -> Self {
+        let mut chunks = Vec::new();
+
+        for (id, info) in functions.iter_values() {
+            if chunks.len() <= id {
+                chunks.resize(id + 1, None);
+            }
+
+            match info {
+                FunctionInfo::Complete { args, body } => {
+                    chunks[id] = Some(codegen::compile_function(args.len(), body));
+                }
+                FunctionInfo::Cached { chunk, .. } => chunks[id] = Some(chunk.clone()),
+                _ => {}
+            }
+        }
+
+        Program {
+            functions: chunks,
+            globals: globals.values(),
+        }
+    }
+
+    pub fn chunk(&self, func: FunctionId) -> Option<&Chunk> {
+        self.functions.get(func)?.as_ref()
+    }
+
+    /// Value of the global, if it has been evaluated.
+    pub fn global(&self, id: GlobalId) -> Option<&Value> {
+        self.globals.get(id)?.as_ref()
+    }
+
+    /// Checks all chunks, so that running the program can't make the vm panic.
+    /// Compiled programs are always valid, but the ones read from files may be corrupted.
+    /// Returns a description of the first problem.
+    pub fn validate(&self) -> Result<(), String> {
+        for (func, chunk) in self.functions.iter().enumerate() {
+            if let Some(chunk) = chunk {
+                chunk
+                    .validate(self)
+                    .map_err(|problem| format!("function #{func}: {problem}"))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Chunk {
+    /// Checks, that all constants and slots exist, that jumps stay inside the code
+    /// and that no instruction pops more values than have been pushed.
+    fn validate(&self, program: &Program) -> Result<(), String> {
+        if self.locals < self.args {
+            return Err(format!(
+                "{} slots can't hold {} arguments",
+                self.locals, self.args
+            ));
+        }
+
+        // Lowest number of values above the slots, each address can be reached with.
+        // Addresses are only visited again, if they can be reached with fewer values.
+        let mut heights: Vec<Option<usize>> = vec![None; self.code.len()];
+        let mut pending = vec![(0, 0)];
+
+        while let Some((address, height)) = pending.pop() {
+            let Some(op) = self.code.get(address) else {
+                return Err(format!(
+                    "execution continues at {address}, after the end of the code"
+                ));
+            };
+
+            if heights[address].is_some_and(|lowest| lowest <= height) {
+                continue;
+            }
+            heights[address] = Some(height);
+
+            let (pops, pushes) = self
+                .stack_effect(op, program)
+                .map_err(|problem| format!("{op:?} at {address}: {problem}"))?;
+            let Some(height) = height.checked_sub(pops) else {
+                return Err(format!(
+                    "{op:?} at {address} pops more values than are on the stack"
+                ));
+            };
+            let height = height + pushes;
+
+            match op {
+                Op::Return | Op::Unmatched => {}
+                Op::Jump(target) => pending.push((*target as usize, height)),
+                Op::JumpIfFalse(target) => {
+                    pending.push((*target as usize, height));
+                    pending.push((address + 1, height));
+                }
+                _ => pending.push((address + 1, height)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Number of values, the instruction pops from the stack and pushes afterwards.
+    fn stack_effect(&self, op: &Op, program: &Program) -> Result<(usize, usize), String> {
+        let effect = match *op {
+            Op::Const(index) if index as usize >= self.constants.len() => {
+                return Err(format!("the chunk has {} constants", self.constants.len()));
+            }
+            Op::GetLocal(slot) | Op::SetLocal(slot) if slot >= self.locals => {
+                return Err(format!("the frame has {} slots", self.locals));
+            }
+            Op::Call { func, args } => match program.chunk(func) {
+                Some(callee) if callee.args != args => {
+                    return Err(format!("function #{func} takes {} arguments", callee.args));
+                }
+                // calling a function, that hasn't been compiled, is a runtime error
+                _ => (args as usize, 1),
+            },
+            Op::Const(_) | Op::GetLocal(_) | Op::GetGlobal(_) => (0, 1),
+            Op::SetLocal(_) | Op::JumpIfFalse(_) | Op::Pop | Op::Unmatched | Op::Return => (1, 0),
+            Op::Custom { args, .. } => (args as usize, 1),
+            Op::NewStruct { fields, .. } => (fields as usize, 1),
+            Op::NewVariant { payload, .. } => (payload as usize, 1),
+            Op::GetField(_) | Op::Payload => (1, 1),
+            Op::TestVariant(_) => (1, 2),
+            Op::Jump(_) => (0, 0),
+        };
+
+        Ok(effect)
+    }
+}
+
+impl fmt::Display for Chunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "args: {}, locals: {}", self.args, self.locals)?;
+
+        for (address, op) in self.code.iter().enumerate() {
+            match op {
+                Op::Const(index) => writeln!(
+                    f,
+                    "{address:>4}  Const {:?}",
+                    self.constants[*index as usize]
+                )?,
+                op => writeln!(f, "{address:>4}  {op:?}")?,
+            }
+        }
+
+        Ok(())
+    }
+}
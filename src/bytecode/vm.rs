@@ -0,0 +1,170 @@
+//! Executes the bytecode of a [`Program`]
+
+use super::{Chunk, Op, Program};
+use crate::{
+    compilation::{run_custom, InterpreterContext, RuntimeError},
+    id::FunctionId,
+    value::Value,
+};
+
+/// Call of a function, that hasn't returned yet
+struct Frame<'p> {
+    chunk: &'p Chunk,
+    /// Address of the next instruction
+    ip: usize,
+    /// Position of the first argument on the stack
+    base: usize,
+}
+
+/// Stack based virtual machine.
+/// Calls don't recurse on the native stack, so deep recursion in solar code is fine.
+pub struct Vm<'a> {
+    program: &'a Program,
+    io: &'a mut InterpreterContext,
+    stack: Vec<Value>,
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(program: &'a Program, io: &'a mut InterpreterContext) -> Self {
+        Vm {
+            program,
+            io,
+            stack: Vec::new(),
+        }
+    }
+
+    /// Calls a compiled function and returns the resulting value.
+    pub fn call(&mut self, func: FunctionId, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        self.stack.clear();
+        self.stack.extend(args);
+
+        let chunk = self.enter(func, self.stack.len())?;
+        let mut frames = vec![Frame {
+            chunk,
+            ip: 0,
+            base: 0,
+        }];
+
+        while let Some(frame) = frames.last_mut() {
+            let chunk = frame.chunk;
+            let op = &chunk.code[frame.ip];
+            frame.ip += 1;
+
+            match op {
+                Op::Const(index) => {
+                    let value = chunk.constants[*index as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::GetLocal(slot) => {
+                    let value = self.stack[frame.base + *slot as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::SetLocal(slot) => {
+                    let value = self.pop();
+                    self.stack[frame.base + *slot as usize] = value;
+                }
+                Op::GetGlobal(id) => {
+                    let value = self
+                        .program
+                        .global(*id)
+                        .cloned()
+                        .ok_or(RuntimeError::GlobalNotEvaluated(*id))?;
+                    self.stack.push(value);
+                }
+                Op::Call { func, args } => {
+                    let base = self.stack.len() - *args as usize;
+                    let chunk = self.enter(*func, *args as usize)?;
+                    frames.push(Frame { chunk, ip: 0, base });
+                }
+                Op::Custom { code, args } => {
+                    let args = self.stack.split_off(self.stack.len() - *args as usize);
+                    let value = run_custom(self.io, code, args)?;
+                    self.stack.push(value);
+                }
+                Op::Jump(address) => frame.ip = *address as usize,
+                Op::JumpIfFalse(address) => match self.pop() {
+                    Value::Bool(true) => {}
+                    Value::Bool(false) => frame.ip = *address as usize,
+                    value => return Err(unexpected("if", &value)),
+                },
+                Op::Pop => {
+                    self.pop();
+                }
+                Op::NewStruct { ty, fields } => {
+                    let fields = self.stack.split_off(self.stack.len() - *fields as usize);
+                    self.stack.push(Value::Struct { ty: *ty, fields });
+                }
+                Op::GetField(field) => match self.pop() {
+                    Value::Struct { mut fields, .. } if (*field as usize) < fields.len() => {
+                        self.stack.push(fields.swap_remove(*field as usize))
+                    }
+                    value => return Err(unexpected("field access", &value)),
+                },
+                Op::NewVariant {
+                    ty,
+                    variant,
+                    payload,
+                } => {
+                    let payload = payload.then(|| Box::new(self.pop()));
+                    self.stack.push(Value::Enum {
+                        ty: *ty,
+                        variant: *variant,
+                        payload,
+                    });
+                }
+                Op::TestVariant(expected) => match self.stack.last() {
+                    Some(Value::Enum { variant, .. }) => {
+                        let matches = variant == expected;
+                        self.stack.push(Value::Bool(matches));
+                    }
+                    value => return Err(unexpected("match", value.unwrap_or(&Value::Void))),
+                },
+                Op::Payload => match self.pop() {
+                    Value::Enum { payload, .. } => {
+                        self.stack.push(payload.map(|p| *p).unwrap_or(Value::Void))
+                    }
+                    value => return Err(unexpected("match", &value)),
+                },
+                Op::Unmatched => match self.pop() {
+                    Value::Enum { variant, .. } => {
+                        return Err(RuntimeError::UnmatchedVariant(variant))
+                    }
+                    value => return Err(unexpected("match", &value)),
+                },
+                Op::Return => {
+                    let value = self.pop();
+                    self.stack.truncate(frame.base);
+                    self.stack.push(value);
+                    frames.pop();
+                }
+            }
+        }
+
+        Ok(self.pop())
+    }
+
+    /// Looks up the chunk of the called function
+    /// and reserves the slots of its let bindings above the arguments.
+    fn enter(&mut self, func: FunctionId, args: usize) -> Result<&'a Chunk, RuntimeError> {
+        let program = self.program;
+        let chunk = program
+            .chunk(func)
+            .ok_or(RuntimeError::FunctionNotCompiled(func))?;
+
+        let base = self.stack.len() - args;
+        self.stack.resize(base + chunk.locals as usize, Value::Void);
+
+        Ok(chunk)
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("value on the stack")
+    }
+}
+
+fn unexpected(instruction: &str, value: &Value) -> RuntimeError {
+    RuntimeError::UnexpectedValue {
+        instruction: instruction.to_string(),
+        got: value.type_as_str(),
+    }
+}
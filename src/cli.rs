@@ -0,0 +1,493 @@
+use std::{
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::{
+    bytecode::{self, Artifact, Cache, Vm},
+    compilation::{CompilerContext, InterpreterContext},
+    diagnostics::Errors,
+    id::{FunctionId, TypeId},
+    project::{
+        fetch_all, read_all_projects, read_all_projects_for_tests, read_empty_project,
+        read_modules, read_sources, ProjectInfo,
+    },
+    repl, util,
+    value::Value,
+};
+
+/// Compiler and interpreter for the solar programming language
+#[derive(Debug, Parser)]
+#[command(name = "solar", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Compiles and runs a project, or runs a program built by `solar build`
+    Run {
+        #[command(flatten)]
+        target: Target,
+        #[arg(long, value_enum, default_value_t)]
+        backend: Backend,
+        /// Compiles all functions from source, instead of loading the ones in .solar-cache/
+        #[arg(long)]
+        no_cache: bool,
+        /// Arguments passed to the entry function, as strings
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// Compiles a project into a single file, that can be run without the sources
+    Build {
+        #[command(flatten)]
+        target: Target,
+        /// Defaults to the name of the project, e.g. `myproject.solc`
+        #[arg(long, short)]
+        output: Option<String>,
+        /// Number of string arguments, the entry function takes
+        #[arg(long, default_value_t = 0)]
+        args: usize,
+    },
+    /// Type-checks a project without running it
+    Check {
+        /// Root directory of the project, containing the solar.yaml, or a single .sol file
+        #[arg(default_value = ".")]
+        dir: String,
+    },
+    /// Runs the tests of a project
+    Test {
+        /// Root directory of the project, containing the solar.yaml, or a single .sol file
+        #[arg(default_value = ".")]
+        dir: String,
+        /// Only runs tests, whose description or module contains this text
+        #[arg(long)]
+        filter: Option<String>,
+    },
+    /// Prints the MIR of the entry function and all functions it calls
+    DumpMir {
+        #[command(flatten)]
+        target: Target,
+    },
+    /// Prints the bytecode of the entry function and all functions it calls
+    DumpBytecode {
+        #[command(flatten)]
+        target: Target,
+    },
+    /// Measures the run time of the entry function on both backends.
+    /// The entry function should not read from stdin.
+    Bench {
+        #[command(flatten)]
+        target: Target,
+        /// How many times the entry function is run on each backend
+        #[arg(long, default_value_t = 10)]
+        iterations: u32,
+        /// Arguments passed to the entry function, as strings
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// Starts an interactive session
+    Repl {
+        /// Project loaded into the session, or a single .sol file.
+        /// Without it, only the std library is available.
+        dir: Option<String>,
+    },
+    /// Downloads all dependencies, that aren't installed yet, from their git repositories
+    Fetch {
+        /// Root directory of the project, containing the solar.yaml
+        #[arg(default_value = ".")]
+        dir: String,
+    },
+    /// Lists all (transitive) dependencies of a project
+    Deps {
+        /// Root directory of the project, containing the solar.yaml
+        #[arg(default_value = ".")]
+        dir: String,
+    },
+}
+
+/// The project and the function to start with
+#[derive(Debug, Args)]
+pub struct Target {
+    /// Root directory of the project, containing the solar.yaml, or a single .sol file.
+    /// `solar run` accepts a .solc file built by `solar build` as well.
+    #[arg(default_value = ".")]
+    pub dir: String,
+    /// Function, that gets called first, e.g. `main` or `cli.main` for one in a submodule.
+    /// Defaults to the entry of the solar.yaml, or `main`
+    #[arg(long)]
+    pub entry: Option<String>,
+}
+
+/// How compiled functions are executed
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum Backend {
+    /// Flat bytecode on a stack based virtual machine
+    #[default]
+    Bytecode,
+    /// Walks the MIR of the functions directly
+    Tree,
+}
+
+/// Executes the command.
+/// Errors of the solar code are reported right away, resulting in a failing exit code.
+/// Other errors (e.g. reading files) are returned.
+pub fn execute(command: Command) -> anyhow::Result<ExitCode> {
+    match command {
+        Command::Run {
+            target,
+            backend,
+            no_cache,
+            args,
+        } => run(&target, backend, !no_cache, args),
+        Command::Build {
+            target,
+            output,
+            args,
+        } => build(&target, output, args),
+        Command::Check { dir } => check(&dir),
+        Command::Test { dir, filter } => test(&dir, filter.as_deref()),
+        Command::DumpMir { target } => dump_mir(&target),
+        Command::DumpBytecode { target } => dump_bytecode(&target),
+        Command::Bench {
+            target,
+            iterations,
+            args,
+        } => bench(&target, iterations, args),
+        Command::Repl { dir } => repl(dir.as_deref()),
+        Command::Fetch { dir } => fetch(&dir),
+        Command::Deps { dir } => deps(&dir),
+    }
+}
+
+fn run(
+    target: &Target,
+    backend: Backend,
+    use_cache: bool,
+    args: Vec<String>,
+) -> anyhow::Result<ExitCode> {
+    if target.dir.ends_with(&format!(".{}", bytecode::EXTENSION)) {
+        return run_artifact(target, args);
+    }
+
+    let project_info = read_all_projects(&target.dir)?;
+    let sources = read_sources(&project_info)?;
+    let modules = read_modules(&project_info, &sources)?;
+    let ctx = CompilerContext::with_default_io(&project_info, modules);
+
+    // only the bytecode can be cached
+    let cache = match backend {
+        Backend::Bytecode if use_cache => Some(Cache::new(&target.dir)),
+        _ => None,
+    };
+
+    if let Some(cache) = &cache {
+        cache.load(&ctx);
+    }
+
+    let Some(function) = compile_entry(&ctx, target, args.len())? else {
+        return Ok(ExitCode::FAILURE);
+    };
+
+    let args = args.into_iter().map(Value::String).collect();
+    match backend {
+        Backend::Bytecode => {
+            let program = ctx.bytecode();
+
+            // a broken cache must not keep the program from running
+            if let Some(cache) = &cache {
+                if let Err(e) = cache.store(&ctx, &program) {
+                    eprintln!("warning: caching compiled functions: {e:#}");
+                }
+            }
+
+            ctx.run_program(&program, function, args)?
+        }
+        Backend::Tree => ctx.run(function, args)?,
+    };
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Runs a program built by `solar build` on the bytecode vm.
+fn run_artifact(target: &Target, args: Vec<String>) -> anyhow::Result<ExitCode> {
+    if target.entry.is_some() {
+        anyhow::bail!("the entry of a compiled program is fixed, when it is built");
+    }
+
+    let artifact = Artifact::read(&target.dir)?;
+    artifact.check_arguments(args.len())?;
+
+    let args = args.into_iter().map(Value::String).collect();
+    let mut io = InterpreterContext::default();
+    Vm::new(&artifact.program, &mut io).call(artifact.entry, args)?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn build(target: &Target, output: Option<String>, args: usize) -> anyhow::Result<ExitCode> {
+    let project_info = read_all_projects(&target.dir)?;
+    let sources = read_sources(&project_info)?;
+    let modules = read_modules(&project_info, &sources)?;
+    let ctx = CompilerContext::with_default_io(&project_info, modules);
+
+    let Some(function) = compile_entry(&ctx, target, args)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+
+    let (_, project) = project_info
+        .get_by_key(&util::target_id())
+        .expect("target project to be read");
+    let name = &project.config.name;
+
+    let output = output.unwrap_or_else(|| format!("{name}.{}", bytecode::EXTENSION));
+    Artifact::build(&ctx, name, function).write(&output)?;
+
+    println!("built {output}");
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn check(dir: &str) -> anyhow::Result<ExitCode> {
+    let project_info = read_all_projects(dir)?;
+    let sources = read_sources(&project_info)?;
+    let modules = read_modules(&project_info, &sources)?;
+    let ctx = CompilerContext::with_default_io(&project_info, modules);
+
+    let errors = ctx.check_target();
+    if !errors.is_empty() {
+        eprintln!("{}", Errors(errors));
+        return Ok(ExitCode::FAILURE);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn test(dir: &str, filter: Option<&str>) -> anyhow::Result<ExitCode> {
+    let project_info = read_all_projects_for_tests(dir)?;
+    let sources = read_sources(&project_info)?;
+    let modules = read_modules(&project_info, &sources)?;
+    let ctx = CompilerContext::with_default_io(&project_info, modules);
+
+    let tests = ctx.target_tests();
+    let total = tests.len();
+
+    let tests = tests
+        .into_iter()
+        .filter(|test| match filter {
+            Some(filter) => {
+                test.description.contains(filter) || test.module().join(".").contains(filter)
+            }
+            None => true,
+        })
+        .collect::<Vec<_>>();
+    let filtered_out = total - tests.len();
+
+    println!("running {} tests", tests.len());
+
+    let mut failures = Vec::new();
+    for test in &tests {
+        let position = match &test.location {
+            Some(location) => format!("{}:{}", location.file, location.line),
+            None => test.module().join("."),
+        };
+        print!("test \"{}\" ({position}) ... ", test.description);
+
+        let result = match ctx.compile_test(&test.symbol_id) {
+            Ok(function) => match ctx.run(function, Vec::new()) {
+                Ok(Value::Bool(true)) => Ok(()),
+                Ok(value) => Err(format!("evaluated to {}", ctx.display_value(&value))),
+                Err(e) => Err(format!("runtime error: {e}")),
+            },
+            Err(e) => Err(format!("error: {e:#}")),
+        };
+
+        match result {
+            Ok(()) => println!("ok"),
+            Err(reason) => {
+                println!("FAILED");
+                failures.push((test, position, reason));
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (test, position, reason) in &failures {
+            println!("\n---- \"{}\" ({position}) ----\n{reason}", test.description);
+        }
+    }
+
+    let passed = tests.len() - failures.len();
+    let status = if failures.is_empty() { "ok" } else { "FAILED" };
+    println!(
+        "\ntest result: {status}. {passed} passed; {} failed; {filtered_out} filtered out",
+        failures.len()
+    );
+
+    if failures.is_empty() {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+fn dump_mir(target: &Target) -> anyhow::Result<ExitCode> {
+    let project_info = read_all_projects(&target.dir)?;
+    let sources = read_sources(&project_info)?;
+    let modules = read_modules(&project_info, &sources)?;
+    let ctx = CompilerContext::with_default_io(&project_info, modules);
+
+    if compile_entry(&ctx, target, 0)?.is_none() {
+        return Ok(ExitCode::FAILURE);
+    }
+
+    print!("{}", ctx.dump_mir());
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn dump_bytecode(target: &Target) -> anyhow::Result<ExitCode> {
+    let project_info = read_all_projects(&target.dir)?;
+    let sources = read_sources(&project_info)?;
+    let modules = read_modules(&project_info, &sources)?;
+    let ctx = CompilerContext::with_default_io(&project_info, modules);
+
+    if compile_entry(&ctx, target, 0)?.is_none() {
+        return Ok(ExitCode::FAILURE);
+    }
+
+    print!("{}", ctx.dump_bytecode());
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn bench(target: &Target, iterations: u32, args: Vec<String>) -> anyhow::Result<ExitCode> {
+    let project_info = read_all_projects(&target.dir)?;
+    let sources = read_sources(&project_info)?;
+    let modules = read_modules(&project_info, &sources)?;
+    let ctx = CompilerContext::with_default_io(&project_info, modules);
+
+    let Some(function) = compile_entry(&ctx, target, args.len())? else {
+        return Ok(ExitCode::FAILURE);
+    };
+    let args = args.into_iter().map(Value::String).collect::<Vec<_>>();
+    let iterations = iterations.max(1);
+
+    let start = Instant::now();
+    let program = ctx.bytecode();
+    let translation = start.elapsed();
+
+    let mut tree = Duration::ZERO;
+    let mut bytecode = Duration::ZERO;
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let expected = ctx.run(function, args.clone())?;
+        tree += start.elapsed();
+
+        let start = Instant::now();
+        let value = ctx.run_program(&program, function, args.clone())?;
+        bytecode += start.elapsed();
+
+        // values don't implement equality, but their representation is unique enough
+        if value.to_string() != expected.to_string() {
+            anyhow::bail!(
+                "backends disagree: tree walking returned {}, bytecode returned {}",
+                ctx.display_value(&expected),
+                ctx.display_value(&value)
+            );
+        }
+    }
+
+    let tree = tree / iterations;
+    let bytecode = bytecode / iterations;
+    println!("translation to bytecode: {translation:?}");
+    println!("tree walking interpreter: {tree:?} per run");
+    println!("bytecode vm:              {bytecode:?} per run");
+    println!(
+        "speedup: {:.2}x",
+        tree.as_secs_f64() / bytecode.as_secs_f64().max(f64::EPSILON)
+    );
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn repl(dir: Option<&str>) -> anyhow::Result<ExitCode> {
+    let project_info = match dir {
+        Some(dir) => read_all_projects(dir)?,
+        None => read_empty_project("repl")?,
+    };
+
+    repl::run(&project_info)?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn fetch(dir: &str) -> anyhow::Result<ExitCode> {
+    let fetched = fetch_all(dir)?;
+
+    for (dep, version) in &fetched {
+        println!("fetched {} {version}", dep.key());
+    }
+
+    if fetched.is_empty() {
+        println!("all dependencies are installed");
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn deps(dir: &str) -> anyhow::Result<ExitCode> {
+    let project_info = read_all_projects(dir)?;
+
+    for (basepath, _, project) in project_info.iter() {
+        if *basepath == util::target_id() {
+            continue;
+        }
+
+        println!("{} ({})", basepath.join(" "), project.fsroot);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Checks the target project and compiles the entry function,
+/// which takes the supplied number of string arguments.
+/// Returns None, if there were errors in the solar code. They have been reported already.
+fn compile_entry<'a>(
+    ctx: &'a CompilerContext<'a>,
+    target: &Target,
+    args: usize,
+) -> anyhow::Result<Option<FunctionId>> {
+    let errors = ctx.check_target();
+    if !errors.is_empty() {
+        eprintln!("{}", Errors(errors));
+        return Ok(None);
+    }
+
+    let entry = entry_name(target, ctx.project_info);
+    let entry = ctx.find_target_function(&entry)?;
+
+    let args = vec![ctx.buildin_types.string as TypeId; args];
+    match ctx.compile_symbol(entry, &args) {
+        Ok((function, _)) => Ok(Some(function)),
+        Err(e) => {
+            eprintln!("{}", Errors(vec![e]));
+            Ok(None)
+        }
+    }
+}
+
+/// The entry given on the command line, or else the one of the solar.yaml.
+fn entry_name(target: &Target, project_info: &ProjectInfo) -> String {
+    match &target.entry {
+        Some(entry) => entry.clone(),
+        None => project_info
+            .get_by_key(&util::target_id())
+            .and_then(|(_, project)| project.config.entry.clone())
+            .unwrap_or_else(|| "main".to_string()),
+    }
+}
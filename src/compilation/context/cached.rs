@@ -0,0 +1,183 @@
+use solar_parser::ast::{self, body::BodyItem};
+
+use super::CompilerContext;
+use crate::{
+    bytecode::Chunk,
+    id::{FunctionId, GlobalId, IdItem, StableId, SymbolId, TypeId, SSID},
+};
+
+/// Function reserved for bytecode loaded from the cache, see [`CompilerContext::reserve_cached`].
+#[derive(Debug)]
+pub struct CachedSlot {
+    pub id: FunctionId,
+    /// Number of arguments, the function takes
+    pub args: usize,
+    ssid: SSID,
+    return_type: TypeId,
+}
+
+/// Functions loaded from the cache, see [`crate::bytecode::Cache`].
+///
+/// The cache refers to functions and types by their [`StableId`],
+/// and to globals by their [`SymbolId`],
+/// because [`FunctionId`]s and [`TypeId`]s depend on the order of compilation.
+/// The cached bytecode is linked to the ids of this compilation, whenever it's loaded.
+///
+/// The cache may have been corrupted, so its ids are checked, before they are resolved.
+/// Ids, that don't refer to a matching declaration, resolve to None.
+impl<'a> CompilerContext<'a> {
+    /// Id of a function or type, that stays the same across compilations.
+    pub fn stable_id(&self, (symbol, args): &SSID) -> StableId {
+        StableId {
+            symbol: symbol.clone(),
+            args: args.iter().map(|arg| self.stable_type(*arg)).collect(),
+        }
+    }
+
+    pub fn stable_type(&self, ty: TypeId) -> StableId {
+        self.stable_id(&self.type_ssid(ty))
+    }
+
+    /// Type of this compilation, the stable id refers to.
+    /// It's laid out, unless it has been before.
+    pub fn cached_type(&'a self, id: &StableId) -> Option<TypeId> {
+        if !matches!(id.symbol.2, IdItem::Type(_)) {
+            return None;
+        }
+        self.cached_declaration(&id.symbol)?;
+
+        let generics = self.cached_types(&id.args)?;
+        self.compile_type(&id.symbol, generics).ok()
+    }
+
+    /// Function of this compilation, the stable id refers to,
+    /// along with the number of arguments it takes.
+    /// It's compiled, unless it has been compiled or loaded before.
+    pub fn cached_function(&'a self, id: &StableId) -> Option<(FunctionId, usize)> {
+        self.cached_declaration(&id.symbol)?;
+
+        let ssid = (id.symbol.clone(), self.cached_types(&id.args)?);
+        let (args, type_hint) = self.ssid_arguments(&ssid)?;
+        let (function, _) = self.compile_hinted_symbol(ssid.0, &args, type_hint).ok()?;
+
+        Some((function, args.len()))
+    }
+
+    /// Global of this compilation, the symbol refers to.
+    /// It's evaluated, unless it has been before.
+    pub fn cached_global(&'a self, symbol: &SymbolId) -> Option<GlobalId> {
+        if !matches!(symbol.2, IdItem::GlobalVar(_)) {
+            return None;
+        }
+        self.cached_declaration(symbol)?;
+
+        self.evaluate_global(symbol).ok().map(|(id, _, _)| id)
+    }
+
+    /// Reserves a function for its cached bytecode,
+    /// so that other cached functions may be linked to it.
+    /// Returns None, if it has been compiled already.
+    pub fn reserve_cached(&'a self, id: &StableId, return_type: &StableId) -> Option<CachedSlot> {
+        self.cached_declaration(&id.symbol)?;
+
+        let ssid = (id.symbol.clone(), self.cached_types(&id.args)?);
+        let (args, _) = self.ssid_arguments(&ssid)?;
+        let return_type = self.cached_type(return_type)?;
+
+        let mut functions = self.functions.write().expect("reserve cached function");
+        if functions.get_by_key(&ssid).is_some() {
+            return None;
+        }
+
+        let id = functions.reserve(ssid.clone(), Some(return_type));
+        Some(CachedSlot {
+            id,
+            args: args.len(),
+            ssid,
+            return_type,
+        })
+    }
+
+    /// Stores the bytecode of a reserved function, once it has been linked.
+    pub fn complete_cached(&self, slot: CachedSlot, chunk: Chunk) {
+        self.functions
+            .write()
+            .expect("store cached function")
+            .update_cached_function(slot.id, slot.ssid.1, slot.return_type, chunk);
+    }
+
+    /// Compiles reserved functions from source instead, because their bytecode couldn't be linked.
+    /// They keep their ids, so functions linked to them stay valid.
+    /// Errors are reported, once the functions are referenced by the program.
+    pub fn discard_cached(&'a self, slots: Vec<CachedSlot>) {
+        {
+            let mut functions = self.functions.write().expect("discard cached functions");
+            for slot in &slots {
+                functions.mark_failed(slot.id);
+            }
+        }
+
+        for slot in slots {
+            if let Some((args, type_hint)) = self.ssid_arguments(&slot.ssid) {
+                let _ = self.compile_hinted_symbol(slot.ssid.0, &args, type_hint);
+            }
+        }
+    }
+
+    fn cached_types(&'a self, ids: &[StableId]) -> Option<Vec<TypeId>> {
+        ids.iter().map(|id| self.cached_type(id)).collect()
+    }
+
+    /// Arguments to compile the function identified by the SSID with, along with the type hint.
+    /// The SSID of enum variants consists of the type arguments of the enum,
+    /// not of the arguments of the constructor, see `compile_variant`.
+    fn ssid_arguments(&'a self, (symbol, args): &SSID) -> Option<(Vec<TypeId>, Option<TypeId>)> {
+        match (symbol.2, self.cached_declaration(symbol)?) {
+            (IdItem::Method(_, variant), BodyItem::TypeDecl(decl))
+                if matches!(decl.fields, ast::EnumOrStructFields::EnumFields(_)) =>
+            {
+                let ty = self.compile_type(symbol, args.clone()).ok()?;
+                let (_, payload) = self.type_variants(ty).get(variant as usize)?.clone();
+
+                Some((Vec::from_iter(payload), Some(ty)))
+            }
+            _ => Some((args.clone(), None)),
+        }
+    }
+
+    /// Declaration of the symbol, if it exists and is of the kind the symbol refers to.
+    /// Tests are never cached, they can't be referenced.
+    fn cached_declaration(&self, (module, file, item): &SymbolId) -> Option<&BodyItem<'a>> {
+        let items = &self
+            .module_info
+            .get(module)?
+            .files
+            .get(*file as usize)?
+            .ast
+            .items;
+
+        match *item {
+            IdItem::Func(index) => items
+                .get(index as usize)
+                .filter(|item| matches!(item, BodyItem::Function(_))),
+            IdItem::GlobalVar(index) => items
+                .get(index as usize)
+                .filter(|item| matches!(item, BodyItem::Let(_))),
+            IdItem::Type(index) => items.get(index as usize).filter(|item| {
+                matches!(item, BodyItem::TypeDecl(_) | BodyItem::BuildinTypeDecl(_))
+            }),
+            IdItem::Method(index, field) => items.get(index as usize).filter(|item| match item {
+                BodyItem::TypeDecl(decl) => match &decl.fields {
+                    ast::EnumOrStructFields::StructFields(fields) => {
+                        (field as usize) < fields.len()
+                    }
+                    ast::EnumOrStructFields::EnumFields(variants) => {
+                        (field as usize) < variants.len()
+                    }
+                },
+                _ => false,
+            }),
+            IdItem::Test(_) => None,
+        }
+    }
+}
@@ -3,8 +3,9 @@ use std::ops::Deref;
 use hotel::HotelMap;
 
 use crate::{
-    mir::StaticExpression,
+    bytecode::Chunk,
     id::{TypeId, SSID},
+    mir::StaticExpression,
 };
 
 #[derive(Debug)]
@@ -12,12 +13,25 @@ pub enum FunctionInfo {
     /// Used to reserve functions.
     /// Needed, because in cases like recursion we need to know
     /// that a function exists, before we have completly compiled it.
-    Partial,
+    /// The return type is known, if it has been declared
+    /// or has been inferred from a base case of the recursion.
+    Partial { return_type: Option<TypeId> },
+    /// Compilation of the function has been attempted, but failed.
+    /// It may be compiled again, e.g. once the return type
+    /// of a recursive call has been inferred.
+    Failed,
     Complete {
         // TODO also add the name of the argument, for error messages.
         args: Vec<TypeId>,
         body: StaticExpression,
     },
+    /// Loaded from the cache, instead of being compiled.
+    /// There is no MIR, only the bytecode, see [`crate::bytecode::Cache`].
+    Cached {
+        args: Vec<TypeId>,
+        return_type: TypeId,
+        chunk: Chunk,
+    },
 }
 
 /// Container to hold information about compiled functions
@@ -32,8 +46,36 @@ impl FunctionStore {
     }
 
     /// Reserve a slot in the hotel map
-    pub fn reserve(&mut self, key: SSID) -> usize {
-        self.functions.insert(key, FunctionInfo::Partial)
+    pub fn reserve(&mut self, key: SSID, return_type: Option<TypeId>) -> usize {
+        self.functions
+            .insert(key, FunctionInfo::Partial { return_type })
+    }
+
+    /// Reserve a slot again, after a previous attempt to compile the function failed.
+    pub fn restart(&mut self, index: usize, return_type: Option<TypeId>) {
+        self.functions
+            .set_by_index(index, FunctionInfo::Partial { return_type });
+    }
+
+    /// Marks a reserved function as failed, so that it may be compiled again later.
+    pub fn mark_failed(&mut self, index: usize) {
+        self.functions.set_by_index(index, FunctionInfo::Failed);
+    }
+
+    /// Sets the return type of a reserved function,
+    /// so that recursive calls to it can be compiled.
+    pub fn set_return_type(&mut self, index: usize, return_type: TypeId) {
+        self.restart(index, Some(return_type));
+    }
+
+    /// Returns the return type of a function, if it is known at this point.
+    pub fn return_type(&self, index: usize) -> Option<TypeId> {
+        match self.functions.get_by_index(index)? {
+            FunctionInfo::Partial { return_type } => *return_type,
+            FunctionInfo::Complete { body, .. } => Some(body.ty),
+            FunctionInfo::Cached { return_type, .. } => Some(*return_type),
+            FunctionInfo::Failed => None,
+        }
     }
 
     pub fn update_complete_function(
@@ -45,6 +87,24 @@ impl FunctionStore {
         self.functions
             .set_by_index(index, FunctionInfo::Complete { args, body });
     }
+
+    /// Completes a function reserved for the bytecode loaded from the cache.
+    pub fn update_cached_function(
+        &mut self,
+        index: usize,
+        args: Vec<TypeId>,
+        return_type: TypeId,
+        chunk: Chunk,
+    ) {
+        self.functions.set_by_index(
+            index,
+            FunctionInfo::Cached {
+                args,
+                return_type,
+                chunk,
+            },
+        );
+    }
 }
 
 impl Deref for FunctionStore {
@@ -0,0 +1,156 @@
+use solar_parser::ast;
+
+use super::{CompilerContext, Lookup};
+use crate::{
+    compilation::CompilationError,
+    id::{SymbolId, TypeId, SSID},
+    util,
+};
+
+/// Type variables bound to concrete types, e.g. `a => Int`
+pub(super) type Bindings = Vec<(String, TypeId)>;
+
+/// Instantiating generic functions and types.
+///
+/// e.g.
+/// type Wrapper a
+///   - value: a
+///
+/// fun unwrap(w: Wrapper a) -> a = value w
+///
+/// Every instantiation is compiled separately and stored under it's own SSID,
+/// e.g. `Wrapper Int` and `Wrapper String` are two distinct types,
+/// `unwrap(Wrapper Int)` and `unwrap(Wrapper String)` two distinct functions.
+impl<'a> CompilerContext<'a> {
+    /// Matches a type annotation against a concrete type,
+    /// binding type variables along the way.
+    /// e.g. unifying `Maybe a` with `Maybe Int` binds `a => Int`
+    /// Returns false, if the annotation can't describe the type.
+    pub(super) fn unify(
+        &'a self,
+        annotation: &ast::Type,
+        ty: TypeId,
+        lookup: &Lookup,
+        is_variable: &dyn Fn(&str) -> bool,
+        bindings: &mut Bindings,
+    ) -> Result<bool, CompilationError> {
+        let path = util::type_path(annotation);
+        let args = util::type_args(annotation);
+
+        if let [name] = path.as_slice() {
+            if args.is_empty() && is_variable(name) {
+                if let Some((_, bound)) = bindings.iter().find(|(n, _)| n == name) {
+                    return Ok(*bound == ty);
+                }
+
+                bindings.push((name.clone(), ty));
+                return Ok(true);
+            }
+        }
+
+        if args.is_empty() {
+            return Ok(self.resolve_type(annotation, lookup)? == ty);
+        }
+
+        // generic type, e.g. `Maybe a`
+        let Some(declaration) = self.find_type_declaration(&path, lookup) else {
+            return Err(CompilationError::UnknownType(path.join(".")));
+        };
+
+        let (symbol, generics) = self.type_ssid(ty);
+        if symbol != declaration || generics.len() != args.len() {
+            return Ok(false);
+        }
+
+        for (arg, generic) in args.iter().zip(generics) {
+            if !self.unify(arg, generic, lookup, is_variable, bindings)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Binds the type variables of a function to the types of the supplied arguments.
+    /// Returns None, if the function can't be called with arguments of these types.
+    pub(super) fn bind_arguments(
+        &'a self,
+        func: &ast::Function,
+        arg_types: &[TypeId],
+        lookup: &Lookup,
+    ) -> Result<Option<Bindings>, CompilationError> {
+        if func.args.len() != arg_types.len() {
+            return Ok(None);
+        }
+
+        let mut bindings = Bindings::new();
+        for ((_ident, ty), arg_type) in func.args.iter().zip(arg_types) {
+            let Some(ty) = ty else {
+                continue;
+            };
+
+            if !self.unify(ty, *arg_type, lookup, &util::is_type_variable, &mut bindings)? {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(bindings))
+    }
+
+    /// Declaration and type arguments of a concrete type.
+    pub(super) fn type_ssid(&self, ty: TypeId) -> SSID {
+        self.types
+            .read()
+            .expect("aquire readlock for types")
+            .get_by_index(ty)
+            .expect("find type in type store")
+            .ssid()
+            .clone()
+    }
+
+    /// Name of an instantiated type, e.g. `Maybe Int` or `Maybe (Wrapper String)`
+    pub(super) fn instance_name(&self, name: &str, generics: &[TypeId]) -> String {
+        let mut instance = name.to_string();
+        for ty in generics {
+            let arg = self.type_name(*ty);
+            if arg.contains(' ') {
+                instance += &format!(" ({arg})");
+            } else {
+                instance += &format!(" {arg}");
+            }
+        }
+
+        instance
+    }
+
+    /// Points an error, that occurred while instantiating a generic definition,
+    /// at the definition.
+    pub(super) fn instantiation_error(
+        &'a self,
+        symbol_id: &SymbolId,
+        types: &[TypeId],
+        error: CompilationError,
+    ) -> CompilationError {
+        CompilationError::Instantiation {
+            definition: self.describe_symbol_id(symbol_id),
+            types: self.type_names(types),
+            error: Box::new(error),
+        }
+    }
+}
+
+/// A function is generic, if any of it's arguments accepts more than one type.
+pub(super) fn is_generic(func: &ast::Function) -> bool {
+    func.args.iter().any(|(_ident, ty)| match ty {
+        Some(ty) => mentions_type_variable(ty),
+        None => true,
+    })
+}
+
+/// Whether a type annotation contains a type variable, e.g. `a` or `Maybe a`
+fn mentions_type_variable(ty: &ast::Type) -> bool {
+    match util::type_path(ty).as_slice() {
+        [name] if util::is_type_variable(name) => true,
+        _ => util::type_args(ty).iter().any(mentions_type_variable),
+    }
+}
@@ -0,0 +1,161 @@
+use std::ops::Deref;
+
+use hotel::HotelMap;
+
+use crate::{
+    id::{GlobalId, SymbolId, TypeId},
+    value::Value,
+};
+
+#[derive(Debug)]
+pub enum GlobalInfo {
+    /// The value of the global is being computed.
+    /// Encountering it again means, that globals depend on each other.
+    Evaluating,
+    /// Compiling or evaluating the global failed.
+    Failed,
+    Complete { value: Value, ty: TypeId },
+}
+
+/// Slot table holding the values of global variables (`let` on module level)
+#[derive(Default)]
+pub struct GlobalStore {
+    globals: HotelMap<SymbolId, GlobalInfo>,
+    /// Globals, that are being evaluated, in the order they have been started.
+    /// Each of them is referenced by the value of the one before.
+    evaluating: Vec<GlobalId>,
+}
+
+impl GlobalStore {
+    pub fn new() -> Self {
+        GlobalStore::default()
+    }
+
+    /// Reserve a slot for the global, before it's value is known.
+    pub fn reserve(&mut self, key: SymbolId) -> GlobalId {
+        let index = self.globals.insert(key, GlobalInfo::Evaluating);
+        self.evaluating.push(index);
+        index
+    }
+
+    /// Evaluate a global again, after a previous attempt failed.
+    pub fn restart(&mut self, index: GlobalId) {
+        self.globals.set_by_index(index, GlobalInfo::Evaluating);
+        self.evaluating.push(index);
+    }
+
+    pub fn mark_failed(&mut self, index: GlobalId) {
+        self.globals.set_by_index(index, GlobalInfo::Failed);
+        self.finish(index);
+    }
+
+    pub fn update_complete_global(&mut self, index: GlobalId, value: Value, ty: TypeId) {
+        self.globals
+            .set_by_index(index, GlobalInfo::Complete { value, ty });
+        self.finish(index);
+    }
+
+    fn finish(&mut self, index: GlobalId) {
+        self.evaluating.retain(|evaluating| *evaluating != index);
+    }
+
+    /// Value of the global, if it has been evaluated.
+    pub fn value(&self, index: GlobalId) -> Option<&Value> {
+        match self.globals.get_by_index(index)? {
+            GlobalInfo::Complete { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Values of all globals, indexed by their [`GlobalId`].
+    /// Globals, that haven't been evaluated, have no value.
+    pub fn values(&self) -> Vec<Option<Value>> {
+        let mut values = Vec::new();
+        for (index, info) in self.globals.iter_values() {
+            if values.len() <= index {
+                values.resize(index + 1, None);
+            }
+
+            if let GlobalInfo::Complete { value, .. } = info {
+                values[index] = Some(value.clone());
+            }
+        }
+
+        values
+    }
+
+    /// Globals, that are being evaluated, starting at the supplied one.
+    /// Globals are evaluated depth first,
+    /// so these are the globals depending on each other.
+    pub fn evaluating_since(&self, index: GlobalId) -> Vec<&SymbolId> {
+        let start = self
+            .evaluating
+            .iter()
+            .position(|evaluating| *evaluating == index)
+            .unwrap_or(self.evaluating.len());
+
+        self.evaluating[start..]
+            .iter()
+            .filter_map(|evaluating| {
+                self.globals
+                    .iter()
+                    .find(|(_, i, _)| i == evaluating)
+                    .map(|(key, _, _)| key)
+            })
+            .collect()
+    }
+}
+
+impl Deref for GlobalStore {
+    type Target = HotelMap<SymbolId, GlobalInfo>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.globals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id::IdItem;
+
+    fn global(item: u16) -> SymbolId {
+        (vec!["main".to_string()], 0, IdItem::GlobalVar(item))
+    }
+
+    #[test]
+    fn cycle_contains_the_globals_evaluated_since() {
+        let mut store = GlobalStore::new();
+        let a = store.reserve(global(0));
+        let b = store.reserve(global(1));
+        store.reserve(global(2));
+
+        assert_eq!(store.evaluating_since(b), vec![&global(1), &global(2)]);
+        assert_eq!(store.evaluating_since(a).len(), 3);
+    }
+
+    #[test]
+    fn completed_globals_are_not_part_of_a_cycle() {
+        let mut store = GlobalStore::new();
+        let a = store.reserve(global(0));
+        let b = store.reserve(global(1));
+        store.update_complete_global(b, Value::Void, 0);
+        store.reserve(global(2));
+
+        assert_eq!(store.evaluating_since(a), vec![&global(0), &global(2)]);
+    }
+
+    #[test]
+    fn restarted_global_follows_the_globals_depending_on_it() {
+        let mut store = GlobalStore::new();
+        let a = store.reserve(global(0));
+        store.mark_failed(a);
+
+        // b is evaluated first now, and depends on a, which got a lower index.
+        let b = store.reserve(global(1));
+        store.restart(a);
+
+        assert_eq!(store.evaluating_since(b), vec![&global(1), &global(0)]);
+        assert_eq!(store.evaluating_since(a), vec![&global(0)]);
+    }
+}
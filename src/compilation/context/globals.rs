@@ -0,0 +1,231 @@
+use std::collections::HashSet;
+
+use solar_parser::ast::body::BodyItem;
+
+use super::{CompilerContext, FunctionInfo, FunctionStore, GlobalInfo};
+use crate::{
+    bytecode::Op,
+    compilation::{interpreter::Interpreter, CompilationError},
+    id::{FunctionId, GlobalId, SymbolId, TypeId},
+    mir::{CustomInstructionCode, Instruction, StaticExpression},
+    util::Scope,
+    value::Value,
+};
+
+/// Global variables
+///
+/// e.g.
+/// let greeting = "Hello"
+///
+/// Globals are evaluated once, when they are referenced for the first time.
+/// Their values are stored in the global slot table.
+/// Since that happens during compilation, their values must not perform IO,
+/// e.g. `let name = readline ()` is rejected.
+impl<'a> CompilerContext<'a> {
+    /// Compiles a reference to a global variable.
+    /// Scalar values are folded into the referencing expression,
+    /// all others are read from the slot table.
+    pub(super) fn compile_global(
+        &'a self,
+        symbol_id: &SymbolId,
+    ) -> Result<StaticExpression, CompilationError> {
+        let (id, value, ty) = self.evaluate_global(symbol_id)?;
+
+        if matches!(value, Value::Int(_) | Value::Float(_) | Value::Bool(_)) {
+            return Ok(Instruction::Const(value).expr(ty));
+        }
+
+        Ok(Instruction::GetGlobalVar(id).expr(ty))
+    }
+
+    /// Compiles and evaluates the value of a global variable,
+    /// unless it has been evaluated before.
+    pub(super) fn evaluate_global(
+        &'a self,
+        symbol_id: &SymbolId,
+    ) -> Result<(GlobalId, Value, TypeId), CompilationError> {
+        let failed_before = {
+            let globals = self.globals.read().expect("aquire readlock for globals");
+
+            match globals.get_by_key(symbol_id) {
+                Some((id, GlobalInfo::Complete { value, ty })) => {
+                    return Ok((id, value.clone(), *ty));
+                }
+                // The value of the global depends on itself,
+                // e.g. let a = b, b = a
+                Some((id, GlobalInfo::Evaluating)) => {
+                    let cycle = globals
+                        .evaluating_since(id)
+                        .into_iter()
+                        .chain([symbol_id])
+                        .map(|global| self.global_name(global))
+                        .collect();
+
+                    return Err(CompilationError::CyclicGlobals(cycle));
+                }
+                Some((id, GlobalInfo::Failed)) => Some(id),
+                None => None,
+            }
+        };
+
+        let id = {
+            let mut globals = self.globals.write().expect("reserve global");
+
+            match failed_before {
+                Some(id) => {
+                    globals.restart(id);
+                    id
+                }
+                None => globals.reserve(symbol_id.clone()),
+            }
+        };
+
+        let result = self.compile_global_value(symbol_id);
+
+        let mut globals = self.globals.write().expect("store global");
+        match result {
+            Ok((value, ty)) => {
+                globals.update_complete_global(id, value.clone(), ty);
+                Ok((id, value, ty))
+            }
+            Err(e) => {
+                globals.mark_failed(id);
+                Err(e)
+            }
+        }
+    }
+
+    fn compile_global_value(
+        &'a self,
+        symbol_id: &SymbolId,
+    ) -> Result<(Value, TypeId), CompilationError> {
+        let (lookup, item) = self.lookup(symbol_id);
+        let BodyItem::Let(var) = item else {
+            unreachable!("global variables are declared by let");
+        };
+
+        let name = var.identifier.value;
+        let file = lookup.file;
+
+        let body = self
+            .compile_full_expression(&var.value, lookup, &mut Scope::new(), None)
+            .map_err(|e| e.in_function(name, file))?;
+
+        let value = {
+            let functions = self.functions.read().expect("aquire readlock for functions");
+
+            if let Some(code) = io_instruction(&body, &functions, &mut HashSet::new()) {
+                return Err(CompilationError::ImpureGlobal {
+                    global: name.to_string(),
+                    instruction: format!("{code:?}"),
+                });
+            }
+
+            let globals = self.globals.read().expect("aquire readlock for globals");
+            let mut io = self.interpreter_ctx.lock().expect("lock interpreter io");
+
+            Interpreter::new(&functions, &globals, &mut io).evaluate(&body)
+        };
+
+        match value {
+            Ok(value) => Ok((value, body.ty)),
+            Err(error) => Err(CompilationError::GlobalEvaluation {
+                global: name.to_string(),
+                error,
+            }),
+        }
+    }
+
+    /// Name of a global variable, as used in error messages.
+    fn global_name(&'a self, symbol_id: &SymbolId) -> String {
+        let (lookup, item) = self.lookup(symbol_id);
+
+        match item {
+            BodyItem::Let(var) => format!("{} ({})", var.identifier.value, lookup.file),
+            _ => unreachable!("global variables are declared by let"),
+        }
+    }
+}
+
+/// Finds an instruction, that performs IO, within the expression
+/// or any of the functions it calls.
+/// Functions, that haven't been compiled completely, are skipped.
+/// Calling them fails anyway.
+fn io_instruction(
+    expr: &StaticExpression,
+    functions: &FunctionStore,
+    visited: &mut HashSet<FunctionId>,
+) -> Option<CustomInstructionCode> {
+    let mut all = |exprs: &[StaticExpression]| {
+        exprs
+            .iter()
+            .find_map(|expr| io_instruction(expr, functions, visited))
+    };
+
+    match expr.instr.as_ref() {
+        Instruction::Custom { code, .. } if code.performs_io() => Some(*code),
+        Instruction::Custom { args, .. } => all(args),
+        Instruction::Const(_) | Instruction::GetLocalVar(_) | Instruction::GetGlobalVar(_) => None,
+        Instruction::FunctionCall { func, args } => {
+            if let Some(code) = all(args) {
+                return Some(code);
+            }
+
+            callee_io_instruction(*func, functions, visited)
+        }
+        Instruction::NewLocalVar {
+            var_value, body, ..
+        } => io_instruction(var_value, functions, visited)
+            .or_else(|| io_instruction(body, functions, visited)),
+        Instruction::IfExpr {
+            condition,
+            case_true,
+            case_false,
+        } => io_instruction(condition, functions, visited)
+            .or_else(|| io_instruction(case_true, functions, visited))
+            .or_else(|| io_instruction(case_false, functions, visited)),
+        Instruction::NewStruct { fields } => all(fields),
+        Instruction::GetField { value, .. } => io_instruction(value, functions, visited),
+        Instruction::NewVariant { payload, .. } => payload
+            .as_ref()
+            .and_then(|payload| io_instruction(payload, functions, visited)),
+        Instruction::MatchVariant {
+            value,
+            cases,
+            default,
+        } => io_instruction(value, functions, visited)
+            .or_else(|| {
+                cases
+                    .iter()
+                    .find_map(|case| io_instruction(&case.body, functions, visited))
+            })
+            .or_else(|| {
+                default
+                    .as_ref()
+                    .and_then(|default| io_instruction(default, functions, visited))
+            }),
+    }
+}
+
+/// Finds an instruction, that performs IO, within the called function,
+/// unless it has been visited before.
+fn callee_io_instruction(
+    func: FunctionId,
+    functions: &FunctionStore,
+    visited: &mut HashSet<FunctionId>,
+) -> Option<CustomInstructionCode> {
+    if !visited.insert(func) {
+        return None;
+    }
+
+    match functions.get_by_index(func) {
+        Some(FunctionInfo::Complete { body, .. }) => io_instruction(body, functions, visited),
+        // functions loaded from the cache only have bytecode
+        Some(FunctionInfo::Cached { chunk, .. }) => chunk.code.iter().find_map(|op| match op {
+            Op::Custom { code, .. } if code.performs_io() => Some(*code),
+            Op::Call { func, .. } => callee_io_instruction(*func, functions, visited),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
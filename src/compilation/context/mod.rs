@@ -1,12 +1,25 @@
+mod cached;
 mod function_store;
-use self::function_store::{FunctionInfo, FunctionStore};
-
-use super::interpreter::InterpreterContext;
-use super::CompilationError;
+mod generics;
+mod global_store;
+mod globals;
+mod resolve;
+mod testcase;
+mod typedecl;
+pub use self::cached::CachedSlot;
+pub use self::function_store::{FunctionInfo, FunctionStore};
+pub use self::global_store::{GlobalInfo, GlobalStore};
+pub use self::testcase::TestCase;
+
+use super::interpreter::{Interpreter, InterpreterContext};
+use super::{CompilationError, RuntimeError};
 use crate::{
+    bytecode::{Program, Vm},
+    diagnostics::Location,
     mir::{CustomInstructionCode, Instruction, StaticExpression},
-    id::{FunctionId, IdItem, IdModule, Symbol, SymbolId, TypeId, SSID},
+    id::{FunctionId, IdFile, IdItem, IdModule, Symbol, SymbolId, TypeId, SSID},
     project::{FileInfo, FindError, GlobalModules, Module, ProjectInfo, SymbolResolver},
+    source_map::{FileId, Span},
     types::{
         buildin::{link_buildin_types, BuildinTypeId},
         Type,
@@ -16,7 +29,10 @@ use crate::{
 };
 use hotel::HotelMap;
 use solar_parser::ast::{self, body::BodyItem, expr::{FullExpression, Literal}};
-use std::sync::{Mutex, RwLock};
+use std::{
+    collections::HashSet,
+    sync::{Mutex, RwLock},
+};
 
 /// Struct that gets created once globally
 /// Containing Information about all Modules, ASTs, Projects
@@ -32,8 +48,20 @@ pub struct CompilerContext<'a> {
     /// Contains static, concrete Type Information.
     pub types: RwLock<HotelMap<SSID, Type>>,
 
+    /// Types, whose fields or variants couldn't be resolved.
+    /// Their entry in `types` is only a placeholder,
+    /// they are laid out again, once they get referenced.
+    failed_types: RwLock<HashSet<TypeId>>,
+
+    /// Types, whose fields or variants are being resolved right now.
+    /// Their entry in `types` is only a placeholder, so fields of their type are stored by reference.
+    laying_out: RwLock<HashSet<TypeId>>,
+
     pub functions: RwLock<FunctionStore>,
 
+    /// Values of global variables, evaluated during compilation.
+    pub globals: RwLock<GlobalStore>,
+
     // TODO remove
     /// Contains runtime configurations, like stdin and stdout
     pub interpreter_ctx: Mutex<InterpreterContext>,
@@ -54,7 +82,10 @@ impl<'a> CompilerContext<'a> {
             module_info,
             interpreter_ctx: Mutex::new(InterpreterContext::default()),
             types,
+            failed_types: Default::default(),
+            laying_out: Default::default(),
             functions,
+            globals: Default::default(),
             buildin_types,
         }
     }
@@ -72,30 +103,237 @@ impl<'a> CompilerContext<'a> {
             IdItem::Func(id) => &fileinfo.ast.items[id as usize],
             IdItem::GlobalVar(id) => &fileinfo.ast.items[id as usize],
             IdItem::Type(id) => &fileinfo.ast.items[id as usize],
-            IdItem::Method(_typeid, _fieldid) => {
-                unimplemented!("accessing derived methods is not yet implemented")
-            }
+            IdItem::Test(id) => &fileinfo.ast.items[id as usize],
+            // Derived methods are part of the type declaration
+            IdItem::Method(typeid, _fieldid) => &fileinfo.ast.items[typeid as usize],
         };
 
         (module, fileinfo, item)
     }
 
-    /// Finds the main function of the current target project
-    pub fn find_target_main(&'a self) -> Result<SymbolId, FindError> {
-        let path = util::target_id();
-        let module = self.module_info.get(&path).unwrap();
+    /// Finds a function in the current target project, e.g. the entry point `main`.
+    /// Functions in submodules are prefixed by the module path, e.g. `cli.main`.
+    pub fn find_target_function(&'a self, entry: &str) -> Result<SymbolId, FindError> {
+        let mut path = util::target_id();
+        let name = match entry.rsplit_once('.') {
+            Some((module, name)) => {
+                path.extend(module.split('.').map(str::to_string));
+                name
+            }
+            None => entry,
+        };
+        let module = self.resolve_module(&path)?;
 
-        let mut candidates = module.find("main", &path)?;
+        let mut candidates = module.find(name, &path)?;
 
         if candidates.len() != 1 {
             return Err(FindError::TooMany {
-                symbol: "main".to_string(),
+                symbol: name.to_string(),
                 module: path,
             });
         }
 
-        let f_main = candidates.pop().unwrap();
-        Ok(f_main)
+        let function = candidates.pop().unwrap();
+        Ok(function)
+    }
+
+    /// Human readable listing of all compiled functions and their MIR.
+    pub fn dump_mir(&'a self) -> String {
+        let functions = self.functions.read().expect("aquire readlock for functions");
+
+        let mut listing = functions.iter().collect::<Vec<_>>();
+        listing.sort_by_key(|(_, id, _)| *id);
+
+        let mut dump = String::new();
+        for (ssid, id, info) in listing {
+            let symbol = self.describe_symbol_id(&ssid.0);
+            let args = self.type_names(&ssid.1).join(", ");
+
+            dump += &format!("#{id} {symbol} [{args}]\n");
+            match info {
+                FunctionInfo::Complete { body, .. } => dump += &format!("{body:#?}\n\n"),
+                FunctionInfo::Cached { .. } => dump += "loaded from the cache\n\n",
+                _ => dump += "not compiled\n\n",
+            }
+        }
+
+        dump
+    }
+
+    /// Compiles all functions and global variables of the current target project.
+    /// Generic functions are skipped, they are checked when they get instantiated.
+    /// All errors are collected, instead of stopping at the first one.
+    pub fn check_target(&'a self) -> Vec<CompilationError> {
+        let target = util::target_id();
+
+        // sorted, so that errors are reported in the same order every time
+        let mut idmodules = self
+            .module_info
+            .keys()
+            .filter(|idmodule| idmodule.starts_with(&target))
+            .collect::<Vec<_>>();
+        idmodules.sort();
+
+        let mut errors = Vec::new();
+        for idmodule in idmodules {
+            let module = &self.module_info[idmodule];
+
+            for (idfile, fileinfo) in module.files.iter().enumerate() {
+                let idfile = idfile as u16;
+
+                for (iditem, item) in fileinfo.ast.items.iter().enumerate() {
+                    let iditem = iditem as u16;
+
+                    let result = match item {
+                        BodyItem::Function(func) => {
+                            let symbol_id = (idmodule.clone(), idfile, IdItem::Func(iditem));
+                            self.check_function(symbol_id, func)
+                        }
+                        BodyItem::Let(_) => {
+                            let symbol_id = (idmodule.clone(), idfile, IdItem::GlobalVar(iditem));
+                            self.compile_global(&symbol_id).map(|_| ())
+                        }
+                        _ => Ok(()),
+                    };
+
+                    if let Err(e) = result {
+                        errors.push(e);
+                    }
+                }
+            }
+        }
+
+        // Failing functions are reported by all their callers as well.
+        let mut seen = HashSet::new();
+        errors.retain(|e| seen.insert(e.to_string()));
+
+        errors
+    }
+
+    /// Compiles a function with the declared argument types, unless it's generic.
+    fn check_function(
+        &'a self,
+        symbol_id: SymbolId,
+        func: &ast::Function,
+    ) -> Result<(), CompilationError> {
+        if generics::is_generic(func) {
+            return Ok(());
+        }
+
+        let (lookup, _) = self.lookup(&symbol_id);
+        let arg_types = func
+            .args
+            .iter()
+            .filter_map(|(_ident, ty)| ty.as_ref())
+            .map(|ty| self.resolve_type(ty, &lookup))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.compile_symbol(symbol_id, &arg_types).map(|_| ())
+    }
+
+    /// Runs a compiled function with the supplied arguments
+    /// and returns the resulting value.
+    pub fn run(&self, func: FunctionId, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        let functions = self.functions.read().expect("aquire readlock for functions");
+        let globals = self.globals.read().expect("aquire readlock for globals");
+        let mut io = self.interpreter_ctx.lock().expect("lock interpreter io");
+
+        Interpreter::new(&functions, &globals, &mut io).call(func, args)
+    }
+
+    /// Runs a compiled function on the bytecode [`Vm`],
+    /// after translating all compiled functions to bytecode.
+    pub fn run_bytecode(&self, func: FunctionId, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        let program = self.bytecode();
+        self.run_program(&program, func, args)
+    }
+
+    /// Bytecode of all functions, that have been compiled so far,
+    /// along with the values of all globals.
+    pub fn bytecode(&self) -> Program {
+        let functions = self.functions.read().expect("aquire readlock for functions");
+        let globals = self.globals.read().expect("aquire readlock for globals");
+
+        Program::compile(&functions, &globals)
+    }
+
+    /// Runs a function of a program, that has been translated before, see [`Self::bytecode`].
+    pub fn run_program(
+        &self,
+        program: &Program,
+        func: FunctionId,
+        args: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let mut io = self.interpreter_ctx.lock().expect("lock interpreter io");
+
+        Vm::new(program, &mut io).call(func, args)
+    }
+
+    /// Human readable listing of the bytecode of all compiled functions.
+    pub fn dump_bytecode(&'a self) -> String {
+        let program = self.bytecode();
+        let functions = self.functions.read().expect("aquire readlock for functions");
+
+        let mut listing = functions.iter().collect::<Vec<_>>();
+        listing.sort_by_key(|(_, id, _)| *id);
+
+        let mut dump = String::new();
+        for (ssid, id, _) in listing {
+            let symbol = self.describe_symbol_id(&ssid.0);
+            let args = self.type_names(&ssid.1).join(", ");
+
+            dump += &format!("#{id} {symbol} [{args}]\n");
+            match program.chunk(id) {
+                Some(chunk) => dump += &format!("{chunk}\n"),
+                None => dump += "not compiled\n\n",
+            }
+        }
+
+        dump
+    }
+
+    /// Compiles a standalone expression,
+    /// as if it was written inside a file of the root module of the target project.
+    /// The expression may refer to the variables in the scope.
+    /// Its source code is given separately, because it's not part of the file (e.g. REPL input).
+    pub fn compile_expression(
+        &'a self,
+        expr: &FullExpression,
+        idfile: IdFile,
+        content: &str,
+        scope: &mut Scope,
+    ) -> Result<StaticExpression, CompilationError> {
+        let idmodule = util::target_id();
+        let module = self.resolve_module(&idmodule)?;
+        let fileinfo = module.files.get(idfile as usize).expect("IdFile to be valid");
+
+        let lookup = Lookup {
+            module,
+            idmodule,
+            imports: &fileinfo.imports,
+            file: &fileinfo.filename,
+            // The expression isn't part of the file, its spans are located within its own content.
+            source: fileinfo.source,
+            function: None,
+            generics: Vec::new(),
+            content,
+        };
+
+        self.compile_full_expression(expr, lookup, scope, None)
+    }
+
+    /// Evaluates an expression compiled by [`Self::compile_expression`].
+    /// The frame contains the values of the variables inside the scope, it was compiled with.
+    pub fn evaluate(
+        &self,
+        expr: &StaticExpression,
+        frame: &mut Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let functions = self.functions.read().expect("aquire readlock for functions");
+        let globals = self.globals.read().expect("aquire readlock for globals");
+        let mut io = self.interpreter_ctx.lock().expect("lock interpreter io");
+
+        Interpreter::new(&functions, &globals, &mut io).evaluate_in(expr, frame)
     }
 
     /// Resolve module based on idpath
@@ -112,6 +350,26 @@ struct Lookup<'a> {
     module: &'a Module<'a>,
     idmodule: IdModule,
     imports: &'a SymbolResolver,
+    /// Name of the file, the symbol is declared in
+    file: &'a str,
+    /// The file inside the source map, the symbol is declared in
+    source: FileId,
+    /// The function currently being compiled, if any.
+    function: Option<FunctionId>,
+    /// Type variables of the instantiation currently being compiled
+    generics: generics::Bindings,
+    /// Source code of the file, used for locating errors
+    content: &'a str,
+}
+
+impl Lookup<'_> {
+    /// Attaches the location of the span to the error, unless it's been located before.
+    fn locate(&self, e: CompilationError, span: &str) -> CompilationError {
+        let location = Span::within(self.source, self.content, span)
+            .and_then(|span| Location::from_span(self.file, self.content, span));
+
+        e.at(location)
+    }
 }
 
 /// Evaluation related stuff.
@@ -123,41 +381,67 @@ impl<'a> CompilerContext<'a> {
         symbol_id: SymbolId,
         args: &[TypeId],
     ) -> Result<(FunctionId, TypeId), CompilationError> {
-        let (module, fileinfo, item) = self.get_symbol(symbol_id.clone());
+        self.compile_hinted_symbol(symbol_id, args, None)
+    }
 
-        let lookup = Lookup {
-            module,
-            idmodule: symbol_id.0.clone(),
-            imports: &fileinfo.imports,
-        };
+    /// Compiles a symbol, that is expected to yield a value of the hinted type.
+    /// The hint supplies the type arguments of generic constants, e.g. `None`.
+    fn compile_hinted_symbol(
+        &'a self,
+        symbol_id: SymbolId,
+        args: &[TypeId],
+        type_hint: Option<TypeId>,
+    ) -> Result<(FunctionId, TypeId), CompilationError> {
+        let (lookup, item) = self.lookup(&symbol_id);
 
         match item {
             BodyItem::Function(func) => self.compile(func, lookup, &(symbol_id, args.to_vec())),
             BodyItem::Let(var) => {
-                // TODO there are no arguments to a global let. the let itself has an expression assigned to it.
+                // there are no arguments to a global let. the let itself has an expression assigned to it.
                 if !args.is_empty() {
                     return Err(CompilationError::CallingVariable {
                         identifer: var.identifier.span.to_string(),
-                        file: fileinfo.filename.to_string(),
+                        file: lookup.file.to_string(),
                     });
                 }
 
-                // TODO for pointers and mutability, you would return the index on the global stack of the variable.
-                // here we just return the value??
-                todo!("value of global variable")
+                // Globals are usually referenced directly, see `compile_global`.
+                // This is only reached, when the global is requested as a function,
+                // so it gets wrapped into one.
+                let global = self.compile_global(&symbol_id)?;
+                self.derived_function((symbol_id, Vec::new()), &[], || Ok(global))
             }
             BodyItem::Test(_) => {
-                unreachable!("SymbolId should never reference Tests in this context")
-            }
-            BodyItem::TypeDecl(_ty) => {
-                unimplemented!("generate functions from types to create types.")
-            }
-            BodyItem::BuildinTypeDecl(_ty) => {
-                unimplemented!("no fields on buildin types")
+                unreachable!("Tests can't be referenced, they are compiled by compile_test")
             }
+            BodyItem::TypeDecl(_) => match symbol_id.2 {
+                IdItem::Method(_, _) => self.compile_derived_method(&symbol_id, args, type_hint),
+                _ => self.compile_constructor(&symbol_id, args),
+            },
+            BodyItem::BuildinTypeDecl(ty) => Err(CompilationError::CallingBuildinType(
+                ty.name.value.to_string(),
+            )),
         }
     }
 
+    /// Lookuptable for resolving symbols from the perspective of the given symbol.
+    fn lookup(&'a self, symbol_id: &SymbolId) -> (Lookup<'a>, &'a BodyItem<'a>) {
+        let (module, fileinfo, item) = self.get_symbol(symbol_id.clone());
+
+        let lookup = Lookup {
+            module,
+            idmodule: symbol_id.0.clone(),
+            imports: &fileinfo.imports,
+            file: &fileinfo.filename,
+            source: fileinfo.source,
+            function: None,
+            generics: Vec::new(),
+            content: fileinfo.content,
+        };
+
+        (lookup, item)
+    }
+
     /// Compile an AST function.
     /// The instructions for the function will get stored inside the context.
     /// All this returns is the lookup symbol/index (and the return type) of the function.
@@ -166,57 +450,86 @@ impl<'a> CompilerContext<'a> {
     fn compile(
         &'a self,
         ast: &ast::Function,
-        lookup: Lookup,
+        mut lookup: Lookup,
         ssid: &SSID,
     ) -> Result<(FunctionId, TypeId), CompilationError> {
         // NOTE: the args to this function are redundant. loopkup and ssid
         // both contain the same IdModule information.
 
         // First, check if function is already compiled
-        {
+        let failed_before = {
             let fnstore = self
                 .functions
                 .read()
                 .expect("aquire readlock for functions");
 
-            if let Some((fnid, info)) = fnstore.get_by_key(&ssid) {
-                match info {
-                    FunctionInfo::Complete { args: _, body } => {
-                        return Ok((fnid, body.ty));
-                    }
-                    // this can happen, when we recursively call a function in solar code.
-                    // e.g. fibonacci 
-                    FunctionInfo::Partial => {
-                        panic!("only found partial function information during compilation. Find out later what to do here");
-                    }
+            match fnstore.get_by_key(ssid) {
+                Some((fnid, FunctionInfo::Complete { args: _, body })) => {
+                    return Ok((fnid, body.ty));
                 }
+                Some((fnid, FunctionInfo::Cached { return_type, .. })) => {
+                    return Ok((fnid, *return_type));
+                }
+                // this can happen, when we recursively call a function in solar code.
+                // e.g. fibonacci
+                // The return type is either declared, or has been inferred from the base case.
+                Some((fnid, FunctionInfo::Partial { return_type })) => {
+                    return match return_type {
+                        Some(ty) => Ok((fnid, *ty)),
+                        None => Err(CompilationError::UnresolvedRecursion {
+                            function: ast.name.value.to_string(),
+                            id: fnid,
+                        }),
+                    };
+                }
+                // A previous attempt failed, e.g. because a recursive call
+                // couldn't be resolved at that point. We just try again.
+                Some((fnid, FunctionInfo::Failed)) => Some(fnid),
+                None => None,
             }
-        }
+        };
 
         // The function is not compiled yet.
         // Compile the function
 
+        // The supplied arguments need to match the declared ones.
+        // This also binds the type variables, e.g. `a` in `fun unwrap(w: Wrapper a) -> a`
+        lookup.generics = self.check_args(ast, &ssid.1, &lookup)?;
+
+        // The declared return type is needed before compiling the body,
+        // because the body may call the function recursively.
+        let declared_return_type = match &ast.return_type {
+            Some(ty) => Some(self.resolve_type(ty, &lookup)?),
+            None => None,
+        };
+
         // First, reserve an index for the function.
         let id = {
-            self.functions
-                .write()
-                .expect("reserve function")
-                .reserve(ssid.clone())
+            let mut fnstore = self.functions.write().expect("reserve function");
+
+            match failed_before {
+                Some(id) => {
+                    fnstore.restart(id, declared_return_type);
+                    id
+                }
+                None => fnstore.reserve(ssid.clone(), declared_return_type),
+            }
         };
+        lookup.function = Some(id);
 
         // Then we can start compiling it.
         // First, add the arguments to the scope.
         let mut scope = Scope::new();
 
+        let function_name = ast.name.value;
+        let file = lookup.file;
+
         let mut types = Vec::new();
         let arg_types = &ssid.1;
         for ((ident, _ty), static_type) in ast.args.iter().zip(arg_types) {
-            // TODO what to do with the arguments type here?
-            // This might be the right place, for
+            // TODO This might be the right place, for
             //     - autocasting integers.
             //     - autocasting to interface types
-            //
-            // if _ty != static_type { return Error }
 
             // we can ignore the index, it's just 1, 2, 3, ... anyway
             let _index = scope.push(ident.value, *static_type);
@@ -224,15 +537,54 @@ impl<'a> CompilerContext<'a> {
             types.push(*static_type);
         }
 
-        // TODO there's a problem here.
-        // Actually we would like to reserve a spot for our function now.
-        // otherwise we can't do recursion.
-
         // compile the static expression
-        let body = self.compile_full_expression(&ast.body, lookup, &mut scope)?;
+        let located = lookup.clone();
+        let body = match self.compile_full_expression(
+            &ast.body,
+            lookup,
+            &mut scope,
+            declared_return_type,
+        ) {
+            Ok(body) => body,
+            Err(e) => {
+                self.functions
+                    .write()
+                    .expect("mark function as failed")
+                    .mark_failed(id);
+
+                // errors, that can't be pinned down further, point at the function.
+                let e = located.locate(e, ast.name.span).in_function(function_name, file);
+                if generics::is_generic(ast) {
+                    return Err(self.instantiation_error(&ssid.0, &ssid.1, e));
+                }
+                return Err(e);
+            }
+        };
 
         let return_type = body.ty;
-        // TODO check, that the return type matches the functions return type.
+
+        // The return type might have been declared, or inferred during recursion.
+        // Either way, the body needs to match it.
+        let expected_return_type = self
+            .functions
+            .read()
+            .expect("aquire readlock for functions")
+            .return_type(id);
+        if let Some(expected) = expected_return_type {
+            if expected != return_type {
+                self.functions
+                    .write()
+                    .expect("mark function as failed")
+                    .mark_failed(id);
+
+                let e = CompilationError::TypeError {
+                    got: self.type_name(return_type),
+                    wanted: self.type_name(expected),
+                    location: None,
+                };
+                return Err(located.locate(e, ast.name.span).in_function(function_name, file));
+            }
+        }
         // TODO possibly map the return value to the type specified in the AST. (e.g. map to interfaces etc.)
 
         // save function
@@ -244,11 +596,53 @@ impl<'a> CompilerContext<'a> {
         Ok((id, return_type))
     }
 
+    /// Checks the supplied argument types against the declaration of the function.
+    /// Arguments without type annotation accept any type.
+    /// Returns the types, that the type variables of the function are bound to.
+    fn check_args(
+        &'a self,
+        ast: &ast::Function,
+        arg_types: &[TypeId],
+        lookup: &Lookup,
+    ) -> Result<generics::Bindings, CompilationError> {
+        let function = ast.name.value;
+
+        if ast.args.len() != arg_types.len() {
+            return Err(CompilationError::ArgumentCount {
+                function: function.to_string(),
+                expected: ast.args.len(),
+                got: arg_types.len(),
+            });
+        }
+
+        let mut bindings = generics::Bindings::new();
+        for ((_ident, ty), static_type) in ast.args.iter().zip(arg_types) {
+            let Some(ty) = ty else {
+                continue;
+            };
+
+            if !self.unify(ty, *static_type, lookup, &util::is_type_variable, &mut bindings)? {
+                return Err(CompilationError::TypeError {
+                    got: self.type_name(*static_type),
+                    wanted: describe_annotation(ty),
+                    location: None,
+                }
+                .in_function(function, lookup.file));
+            }
+        }
+
+        Ok(bindings)
+    }
+
+    /// Compiles an expression.
+    /// The type hint is the type the expression is expected to have, if known.
+    /// It's used to select between overloaded functions.
     fn compile_full_expression(
         &'a self,
         expr: &FullExpression,
         lookup: Lookup,
         scope: &mut Scope,
+        type_hint: Option<TypeId>,
     ) -> Result<StaticExpression, CompilationError> {
         match expr {
             FullExpression::Let(expr) => {
@@ -265,13 +659,14 @@ impl<'a> CompilerContext<'a> {
                 // Insert all let bindings into scope
                 // and evaluate their expressions
                 for (ident, value) in &expr.definitions {
-                    let var_value = self.compile_full_expression(value, lookup.clone(), scope)?;
+                    let var_value = self.compile_full_expression(value, lookup.clone(), scope, None)?;
                     let var_index = scope.push(ident, var_value.ty);
                     let_list.push((var_index, var_value));
                 }
 
                 // We now have readied the scope and are able to evaluate the body
-                let body_expression = self.compile_full_expression(&expr.body, lookup, scope)?;
+                let body_expression =
+                    self.compile_full_expression(&expr.body, lookup, scope, type_hint)?;
 
                 // It's only now that we know the final return type of the let bindings.
                 // It's the one from the body. We can start with building the tree now, in reverse order :)
@@ -325,10 +720,86 @@ impl<'a> CompilerContext<'a> {
                 })
             }
 
-            FullExpression::Expression(ref expr) => self.compile_call_or_value(expr, lookup, scope),
+            FullExpression::IfExpr(expr) => {
+                let condition = self.compile_full_expression(
+                    &expr.condition,
+                    lookup.clone(),
+                    scope,
+                    Some(self.buildin_types.bool as TypeId),
+                )?;
+                self.assert_type_ids(
+                    std::slice::from_ref(&condition),
+                    self.buildin_types.bool,
+                    "Bool",
+                )?;
+
+                // Both branches get their own scope.
+                // Only one of them will be evaluated, so they may share variable slots.
+                let case_true = self.compile_full_expression(
+                    &expr.case_true,
+                    lookup.clone(),
+                    &mut scope.clone(),
+                    type_hint,
+                );
+                let case_false = self.compile_full_expression(
+                    &expr.case_false,
+                    lookup.clone(),
+                    &mut scope.clone(),
+                    type_hint,
+                );
+
+                // If one of the branches calls the function we're compiling recursively,
+                // the return type of the function might not be known yet.
+                // In that case we take the type of the other branch (the base case)
+                // as return type of the function and compile the recursive branch again.
+                let (case_true, case_false) = match (case_true, case_false) {
+                    (Ok(case_true), Ok(case_false)) => (case_true, case_false),
+                    (Err(e), Ok(case_false)) if is_own_recursion(&e, &lookup) => {
+                        self.infer_return_type(&lookup, case_false.ty);
+                        let case_true = self.compile_full_expression(
+                            &expr.case_true,
+                            lookup,
+                            &mut scope.clone(),
+                            type_hint,
+                        )?;
+                        (case_true, case_false)
+                    }
+                    (Ok(case_true), Err(e)) if is_own_recursion(&e, &lookup) => {
+                        self.infer_return_type(&lookup, case_true.ty);
+                        let case_false = self.compile_full_expression(
+                            &expr.case_false,
+                            lookup,
+                            &mut scope.clone(),
+                            type_hint,
+                        )?;
+                        (case_true, case_false)
+                    }
+                    (Err(e), _) | (_, Err(e)) => return Err(e),
+                };
+
+                if case_true.ty != case_false.ty {
+                    return Err(CompilationError::TypeError {
+                        got: self.type_name(case_false.ty),
+                        wanted: self.type_name(case_true.ty),
+                        location: None,
+                    });
+                }
+
+                let ty = case_true.ty;
+                Ok(Instruction::IfExpr {
+                    condition,
+                    case_true,
+                    case_false,
+                }
+                .expr(ty))
+            }
+            FullExpression::Match(expr) => self.compile_match(expr, lookup, scope, type_hint),
+            FullExpression::Expression(ref expr) => {
+                self.compile_call_or_value(expr, lookup, scope, type_hint)
+            }
             FullExpression::Concat(expr) => {
                 let e = expr.to_expr();
-                self.compile_call_or_value(&e, lookup, scope)
+                self.compile_call_or_value(&e, lookup, scope, type_hint)
             }
             expr => panic!("Unexpected type of expression: {expr:#?}"),
         }
@@ -339,6 +810,7 @@ impl<'a> CompilerContext<'a> {
         expr: &ast::expr::Expression,
         lookup: Lookup,
         scope: &mut Scope,
+        type_hint: Option<TypeId>,
     ) -> Result<StaticExpression, CompilationError> {
         match expr {
             // Note, that this may just be loading a variable
@@ -352,12 +824,17 @@ impl<'a> CompilerContext<'a> {
                 let args = fc
                     .args
                     .iter()
-                    .map(|arg| self.compile_value(&arg.value, lookup.clone(), scope))
+                    .map(|arg| self.compile_value(&arg.value, lookup.clone(), scope, None))
                     .collect::<Result<Vec<_>, _>>()?;
 
+                // Errors regarding the call itself point at the function name
+                let located = lookup.clone();
+                let span = fc.function_name.value[0].span;
+                let locate = |e| located.locate(e, span);
+
                 // See, if we're calling a special buildin function
                 if let Some(custom_code) = self.check_buildin_func(fc, &args) {
-                    let (custom_code, ty) = custom_code?;
+                    let (custom_code, ty) = custom_code.map_err(locate)?;
                     return Ok(StaticExpression {
                         instr: Box::new(Instruction::Custom {
                             code: custom_code,
@@ -370,22 +847,15 @@ impl<'a> CompilerContext<'a> {
                 // Find function name in scope
                 let path = util::normalize_path(&fc.function_name);
 
-                let first_arg_ty: Vec<_> = args.iter().map(|s| s.ty).collect();
-
-                let mut symbol_candidates =
-                    self.resolve_symbol(&path, lookup, &first_arg_ty, scope)?;
+                let argsty: Vec<_> = args.iter().map(|s| s.ty).collect();
 
-                // TODO check all candidates first!
-                if symbol_candidates.len() > 1 {
-                    panic!(
-                        "found multiple ({}) candidates for {path:?}",
-                        symbol_candidates.len()
-                    );
-                }
+                let symbol_candidates = self.find_candidates(&path, lookup, scope);
 
                 // The symbol might be a symbol in a module (Function, Constant, Type etc.)
                 // Or just a local variable
-                let symbol = symbol_candidates.pop().unwrap();
+                let symbol = self
+                    .find_appropriate(&path, symbol_candidates, &argsty, type_hint)
+                    .map_err(locate)?;
 
                 // If we have any sort of function or callable stuff, call it.
                 // If we don't have callable stuff, but we have arguments, that's an error
@@ -397,15 +867,21 @@ impl<'a> CompilerContext<'a> {
                         // This would be an important place for them.
                         Ok(Instruction::GetLocalVar(addr.into()).expr(ty))
                     }
+                    Symbol::Global(symbol_id)
+                        if matches!(symbol_id.2, IdItem::GlobalVar(_)) && args.is_empty() =>
+                    {
+                        self.compile_global(&symbol_id).map_err(locate)
+                    }
                     Symbol::Global(symbol_id) => {
-                        let argsty = args.iter().map(|a| a.ty).collect::<Vec<_>>();
-                        let (func, ty) = self.compile_symbol(symbol_id, &argsty)?;
+                        let (func, ty) = self
+                            .compile_hinted_symbol(symbol_id, &argsty, type_hint)
+                            .map_err(locate)?;
 
                         Ok(Instruction::FunctionCall { func, args }.expr(ty))
                     }
                 }
             }
-            ast::expr::Expression::Value(value) => self.compile_value(value, lookup, scope),
+            ast::expr::Expression::Value(value) => self.compile_value(value, lookup, scope, type_hint),
         }
     }
 
@@ -414,10 +890,15 @@ impl<'a> CompilerContext<'a> {
         expr: &ast::expr::Value,
         lookup: Lookup,
         scope: &mut Scope,
+        type_hint: Option<TypeId>,
     ) -> Result<StaticExpression, CompilationError> {
         use ast::expr::Value as V;
         match expr {
-            V::Literal(lit) => compile_constant_value(lit, &self.buildin_types),
+            V::Literal(lit) => compile_constant_value(lit, &self.buildin_types)
+                .map_err(|e| match literal_span(lit) {
+                    Some(span) => lookup.locate(e, span),
+                    None => e,
+                }),
             V::FullIdentifier(path) => {
                 // examples for identifierpath:
                 // point.x
@@ -433,21 +914,20 @@ impl<'a> CompilerContext<'a> {
                 // Actually, I don't think I want to allow Paths here.
                 // just field access.
                 // this line is likely to be deleted.
+                let located = lookup.clone();
+                let span = path.value[0].span;
+                let locate = |e| located.locate(e, span);
+
                 let path = util::normalize_path(path);
 
                 if path.len() != 1 {
                     unimplemented!("field access is not supported as of now.");
                 }
 
-                let mut symbols = self.resolve_symbol(&path, lookup, &[], scope)?;
-                if symbols.len() != 1 {
-                    if symbols.is_empty() {
-                        panic!("no results looking up {path:?}")
-                    }
-                    panic!("found multiple results for {path:?}")
-                }
-
-                let symbol = symbols.pop().unwrap();
+                let candidates = self.find_candidates(&path, lookup, scope);
+                let symbol = self
+                    .find_appropriate(&path, candidates, &[], type_hint)
+                    .map_err(locate)?;
 
                 /* 
                     Note, if we have a function here, we don't want to do a functioncall.
@@ -458,15 +938,19 @@ impl<'a> CompilerContext<'a> {
 
                 match symbol {
                     Symbol::LocalVar { addr, ty } => Ok(Instruction::GetLocalVar(addr as usize).expr(ty)),
+                    Symbol::Global(symbol_id) if matches!(symbol_id.2, IdItem::GlobalVar(_)) => {
+                        self.compile_global(&symbol_id).map_err(locate)
+                    }
                     Symbol::Global(symbol_id) => {
-                        // TODO we can't do that here
-                        // because we DO NOT KNOW the kinds of arguments needed!
-
-                        // TODO find out, what is happening here.
-
-                        panic!("trouble compiling symbol {symbol_id:?}");
-
-                        // Ok(Instruction::FunctionCall {func, args}.expr(ty))
+                        // TODO once we have lambdas, we might want to return
+                        // a reference to the function instead.
+                        // For now a bare identifier is a call without arguments.
+                        // e.g. `fun main() = two`
+                        let (func, ty) = self
+                            .compile_hinted_symbol(symbol_id, &[], type_hint)
+                            .map_err(locate)?;
+
+                        Ok(Instruction::FunctionCall { func, args: Vec::new() }.expr(ty))
                     }
                 }
             }
@@ -476,12 +960,102 @@ impl<'a> CompilerContext<'a> {
                 }
                 let expr = &expr.values[0];
 
-                self.compile_full_expression(expr, lookup, scope)
+                self.compile_full_expression(expr, lookup, scope, type_hint)
             }
             _ => panic!("evaluation not ready for \n{expr:#?}"),
         }
     }
 
+    /// Sets the return type of the function currently compiled,
+    /// so that recursive calls to it can be resolved.
+    fn infer_return_type(&self, lookup: &Lookup, ty: TypeId) {
+        let Some(id) = lookup.function else {
+            return;
+        };
+
+        self.functions
+            .write()
+            .expect("store inferred return type")
+            .set_return_type(id, ty);
+    }
+
+    /// Resolves a type annotation (e.g. `-> Int` or `-> Maybe a`) to the static type it refers to.
+    fn resolve_type(&'a self, ty: &ast::Type, lookup: &Lookup) -> Result<TypeId, CompilationError> {
+        let path = util::type_path(ty);
+
+        // type variables, that are bound in the current instantiation
+        if let [name] = path.as_slice() {
+            if let Some((_, ty)) = lookup.generics.iter().find(|(n, _)| n == name) {
+                return Ok(*ty);
+            }
+        }
+
+        let generics = util::type_args(ty)
+            .iter()
+            .map(|arg| self.resolve_type(arg, lookup))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Buildin types are already known,
+        // type declarations might need to be compiled first.
+        if let Some(symbol_id) = self.find_type_declaration(&path, lookup) {
+            return self.compile_type(&symbol_id, generics);
+        }
+
+        // Buildin types are available everywhere,
+        // just like the types of literals.
+        if let [name] = path.as_slice() {
+            if let Some(id) = self.buildin_types.by_name(name) {
+                let types = self.types.read().expect("aquire readlock for types");
+                let linked = types
+                    .get_by_index(id as TypeId)
+                    .map(|ty| &ty.info_name == name)
+                    .unwrap_or(false);
+
+                if linked {
+                    return Ok(id as TypeId);
+                }
+            }
+        }
+
+        let e = CompilationError::UnknownType(path.join("."));
+        Err(lookup.locate(e, ty.path.value[0].span))
+    }
+
+    /// Finds the type declaration, that a path in a type annotation refers to.
+    fn find_type_declaration(&'a self, path: &[String], lookup: &Lookup) -> Option<SymbolId> {
+        self.find_candidates(path, lookup.clone(), &Scope::new())
+            .into_iter()
+            .find_map(|symbol| match symbol {
+                Symbol::Global(symbol_id) if matches!(symbol_id.2, IdItem::Type(_)) => {
+                    Some(symbol_id)
+                }
+                _ => None,
+            })
+    }
+
+    /// Name of the type, as used in error messages.
+    pub fn type_name(&self, ty: TypeId) -> String {
+        self.types
+            .read()
+            .map(|map| {
+                // Lookup Type info
+                let ty = map.get_by_index(ty).expect("find type in type store");
+                ty.info_name.clone()
+            })
+            .expect("to lookup name of type")
+    }
+
+    /// Displays a value along with the names of the variants of enums, e.g. `Some(5)`.
+    pub fn display_value(&self, value: &Value) -> String {
+        let types = self.types.read().expect("aquire readlock for types");
+        let variant_name = |ty: TypeId, variant: u16| {
+            let (name, _) = types.get_by_index(ty)?.variants().get(variant as usize)?;
+            Some(name.clone())
+        };
+
+        value.named(&variant_name).to_string()
+    }
+
     /// Returns a set of candidates for the symbol.
     /// Resolving the candidates requires further knowledge.
     ///
@@ -509,17 +1083,20 @@ impl<'a> CompilerContext<'a> {
     /// candidates.append_all(find_in_module(full_path))
     ///
     /// return candidates
-    fn resolve_symbol(
+    ///
+    /// The candidates are returned regardless of their signature.
+    /// See [`Self::find_appropriate`] for selecting one of them.
+    fn find_candidates(
         &'a self,
         path: &[String],
         Lookup {
             module,
             idmodule,
             imports,
+            ..
         }: Lookup,
-        arg_types: &[TypeId],
         scope: &Scope,
-    ) -> Result<Vec<Symbol>, CompilationError> {
+    ) -> Vec<Symbol> {
         // TODO check if it was found before, and return compiled version
 
         // if the length of the path is > 1, it's guaranteed looking up an import.
@@ -535,7 +1112,7 @@ impl<'a> CompilerContext<'a> {
             if let Some((ty, addr)) = scope.get(name) {
                 let symbol = Symbol::LocalVar { addr, ty };
 
-                return Ok(vec![symbol]);
+                return vec![symbol];
 
                 // TODO this is the place where we can return references
                 // e.g. in order to assign to stuff.
@@ -606,7 +1183,87 @@ impl<'a> CompilerContext<'a> {
             }
         }
 
-        Ok(candidates)
+        candidates
+    }
+
+    /// Removes functions, that can't be called with arguments of these types.
+    fn filter_by_signature(
+        &'a self,
+        candidates: Vec<Symbol>,
+        arg_types: &[TypeId],
+    ) -> Result<Vec<Symbol>, CompilationError> {
+        let mut matching = Vec::with_capacity(candidates.len());
+        for candidate in candidates {
+            if let Symbol::Global(symbol_id) = &candidate {
+                if !self.signature_matches(symbol_id, arg_types)? {
+                    continue;
+                }
+            }
+
+            matching.push(candidate);
+        }
+
+        Ok(matching)
+    }
+
+    /// Checks, whether a function can be called with arguments of the supplied types,
+    /// according to it's declaration.
+    /// Arguments without type annotations match any type.
+    /// Symbols, that aren't functions, always match.
+    fn signature_matches(
+        &'a self,
+        symbol_id: &SymbolId,
+        arg_types: &[TypeId],
+    ) -> Result<bool, CompilationError> {
+        match symbol_id.2 {
+            IdItem::Func(_) => {}
+            IdItem::Method(_, _) => return self.method_matches(symbol_id, arg_types),
+            _ => return Ok(true),
+        }
+
+        let (lookup, item) = self.lookup(symbol_id);
+        let BodyItem::Function(func) = item else {
+            return Ok(true);
+        };
+
+        Ok(self.bind_arguments(func, arg_types, &lookup)?.is_some())
+    }
+}
+
+/// Checks, whether the error results from calling the function currently compiled recursively,
+/// before it's return type is known.
+fn is_own_recursion(e: &CompilationError, lookup: &Lookup) -> bool {
+    match e {
+        CompilationError::UnresolvedRecursion { id, .. } => Some(*id) == lookup.function,
+        // the recursion might go through another generic function
+        CompilationError::Instantiation { error, .. } => is_own_recursion(error, lookup),
+        // calls are located at their span
+        CompilationError::Located { error, .. } => is_own_recursion(error, lookup),
+        _ => false,
+    }
+}
+
+/// Type annotation as written in the source, e.g. `Maybe a`
+fn describe_annotation(ty: &ast::Type) -> String {
+    let mut annotation = util::type_path(ty).join(".");
+    for arg in util::type_args(ty) {
+        let arg = describe_annotation(arg);
+        if arg.contains(' ') {
+            annotation += &format!(" ({arg})");
+        } else {
+            annotation += &format!(" {arg}");
+        }
+    }
+
+    annotation
+}
+
+/// Span of literals, that may fail to compile
+fn literal_span<'s>(literal: &Literal<'s>) -> Option<&'s str> {
+    match literal {
+        Literal::Int(int) => Some(int.digits),
+        Literal::Float(f) => Some(*f),
+        _ => None,
     }
 }
 
@@ -662,9 +1319,15 @@ impl<'a> CompilerContext<'a> {
 
         let res = match shortened {
             "str_concat" => self.buildin_str_concat(args),
+            "str_length" => self.buildin_str_length(args),
             "identity" => self.buildin_identity(args),
             "readline" => self.buildin_readline(args),
             "print" => self.buildin_print(args),
+            "int_add" => self.buildin_int_arithmetic(args, CustomInstructionCode::IntAdd),
+            "int_sub" => self.buildin_int_arithmetic(args, CustomInstructionCode::IntSub),
+            "int_mul" => self.buildin_int_arithmetic(args, CustomInstructionCode::IntMul),
+            "int_lt" => self.buildin_int_compare(args, CustomInstructionCode::IntLt),
+            "int_eq" => self.buildin_int_compare(args, CustomInstructionCode::IntEq),
 
             _ => Err(CompilationError::WrongBuildin {
                 found: fname.to_string(),
@@ -684,20 +1347,11 @@ impl<'a> CompilerContext<'a> {
         // verify that all args are strings.
         for arg in args {
             if arg.ty != wanted_id as TypeId {
-                let typename = self
-                    .types
-                    .read()
-                    .map(|map| {
-                        // Lookup Type info
-                        let ty = map.get_by_index(arg.ty).expect("find type in type store");
-                        ty.info_name.clone()
-                    })
-                    .expect("to lookup name of type");
-
                 return Err(CompilationError::TypeError {
-                    got: typename,
+                    got: self.type_name(arg.ty),
                     // TODO maybe look up in type info directly
                     wanted: wanted.to_string(),
+                    location: None,
                 });
             }
         }
@@ -716,6 +1370,23 @@ impl<'a> CompilerContext<'a> {
         ))
     }
 
+    pub(crate) fn buildin_str_length(
+        &self,
+        args: &[StaticExpression],
+    ) -> Result<(CustomInstructionCode, TypeId), CompilationError> {
+        if args.len() != 1 {
+            return Err(CompilationError::WrongBuildin {
+                found: "str_length is only implemented for 1 (String) argument".to_string(),
+            });
+        }
+
+        self.assert_type_ids(args, self.buildin_types.string, "String")?;
+        Ok((
+            CustomInstructionCode::StrLength,
+            self.buildin_types.int as TypeId,
+        ))
+    }
+
     pub(crate) fn buildin_print(
         &self,
         args: &[StaticExpression],
@@ -746,6 +1417,49 @@ impl<'a> CompilerContext<'a> {
         Ok((CustomInstructionCode::Identity, args[0].ty))
     }
 
+    /// Assert that there are exactly two arguments of the same integer type.
+    /// Returns that type.
+    fn assert_int_args(&self, args: &[StaticExpression]) -> Result<TypeId, CompilationError> {
+        if args.len() != 2 {
+            return Err(CompilationError::WrongBuildin {
+                found: "integer operations are only implemented with 2 arguments".to_string(),
+            });
+        }
+
+        let ty = args[0].ty;
+        if !self.buildin_types.is_int(ty) {
+            return Err(CompilationError::TypeError {
+                got: self.type_name(ty),
+                wanted: "Integer".to_string(),
+                location: None,
+            });
+        }
+
+        self.assert_type_ids(args, ty as u8, &self.type_name(ty))?;
+
+        Ok(ty)
+    }
+
+    pub(crate) fn buildin_int_arithmetic(
+        &self,
+        args: &[StaticExpression],
+        code: CustomInstructionCode,
+    ) -> Result<(CustomInstructionCode, TypeId), CompilationError> {
+        let ty = self.assert_int_args(args)?;
+
+        Ok((code, ty))
+    }
+
+    pub(crate) fn buildin_int_compare(
+        &self,
+        args: &[StaticExpression],
+        code: CustomInstructionCode,
+    ) -> Result<(CustomInstructionCode, TypeId), CompilationError> {
+        self.assert_int_args(args)?;
+
+        Ok((code, self.buildin_types.bool as TypeId))
+    }
+
     pub(crate) fn buildin_readline(
         &self,
         args: &[StaticExpression],
@@ -770,3 +1484,104 @@ impl<'a> CompilerContext<'a> {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::*;
+    use crate::{
+        project::{read_all_projects, read_modules, read_sources},
+        types::REFERENCE_SIZE,
+    };
+
+    /// Project in a temporary directory, depending on the std library shipped with the repository.
+    /// The directory is removed, once the project is dropped.
+    struct TempProject(PathBuf);
+
+    impl TempProject {
+        fn new(test: &str, code: &str) -> Self {
+            let root =
+                std::env::temp_dir().join(format!("solar-compile-{}-{test}", std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(&root).unwrap();
+
+            let std = concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/solarpath/libraries/std(solar-lang)/0.0.1"
+            );
+            let yaml = format!(
+                "name: test\nversion: 0.1.0\ndependencies:\n  std(solar-lang): {{ path: \"{std}\" }}\n"
+            );
+            fs::write(root.join("solar.yaml"), yaml).unwrap();
+            fs::write(root.join("main.sol"), code).unwrap();
+
+            TempProject(root)
+        }
+    }
+
+    impl Drop for TempProject {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Builds the compiler context for a project consisting of the given main.sol
+    fn compile<T>(
+        test: &str,
+        code: &str,
+        check: impl for<'c> FnOnce(&'c CompilerContext<'c>) -> T,
+    ) -> T {
+        let project = TempProject::new(test, code);
+        let projects = read_all_projects(project.0.to_str().unwrap()).unwrap();
+        let sources = read_sources(&projects).unwrap();
+        let modules = read_modules(&projects, &sources).unwrap();
+
+        let ctx = CompilerContext::with_default_io(&projects, modules);
+        check(&ctx)
+    }
+
+    #[test]
+    fn stores_mutually_recursive_types_by_reference() {
+        let code = "use @std.types.int.Int\n\
+                    \n\
+                    type A\n  - value: Int\n  - next: B\n\
+                    \n\
+                    type B\n  - previous: A\n";
+
+        compile("mutual-types", code, |ctx| {
+            let a = ctx.find_target_function("A").unwrap();
+            let a = ctx.compile_type(&a, Vec::new()).unwrap();
+
+            let types = ctx.types.read().unwrap();
+            let int = types.get_by_index(ctx.buildin_types.int as TypeId).unwrap();
+            let a = types.get_by_index(a).unwrap();
+            let (_, _, b) = a.get_field("next").unwrap();
+            let b = types.get_by_index(b).unwrap();
+
+            assert_eq!(b.size(), REFERENCE_SIZE);
+            assert_eq!(a.size(), int.size() + REFERENCE_SIZE);
+        });
+    }
+
+    #[test]
+    fn infers_return_type_of_self_recursive_function() {
+        let code = "use @std.types.int.(Int, add, sub, lessThan)\n\
+                    \n\
+                    fun main() = fib 10\n\
+                    \n\
+                    fun fib(n: Int) =\n\
+                    \x20   if lessThan n 2\n\
+                    \x20   then n\n\
+                    \x20   else add (fib (sub n 1)) (fib (sub n 2))\n";
+
+        compile("recursion", code, |ctx| {
+            let main = ctx.find_target_function("main").unwrap();
+            let (main, ty) = ctx.compile_symbol(main, &[]).unwrap();
+            assert_eq!(ty, ctx.buildin_types.int as TypeId);
+
+            let value = ctx.run(main, Vec::new()).unwrap();
+            assert_eq!(ctx.display_value(&value), "55");
+        });
+    }
+}
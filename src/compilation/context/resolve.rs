@@ -0,0 +1,171 @@
+use solar_parser::ast::body::BodyItem;
+
+use super::CompilerContext;
+use crate::{
+    compilation::CompilationError,
+    id::{Symbol, SymbolId, TypeId},
+    util,
+};
+
+/// Selecting exactly one candidate for a symbol.
+/// See Documentation/resolving.md
+impl<'a> CompilerContext<'a> {
+    /// Selects the candidate, that fits the supplied arguments (and type hint) best.
+    ///
+    /// If there is only one candidate, it's returned right away.
+    /// Mismatching types will be reported when compiling it.
+    /// Otherwise all candidates, that can't be called with the supplied arguments
+    /// are filtered out. If multiple candidates remain,
+    /// the type hint is used to select the one with the expected return type.
+    pub(super) fn find_appropriate(
+        &'a self,
+        path: &[String],
+        mut candidates: Vec<Symbol>,
+        arg_types: &[TypeId],
+        type_hint: Option<TypeId>,
+    ) -> Result<Symbol, CompilationError> {
+        let symbol = path.join(".");
+
+        if candidates.is_empty() {
+            return Err(CompilationError::NoCandidates {
+                symbol,
+                args: self.type_names(arg_types),
+                candidates: Vec::new(),
+            });
+        }
+
+        if candidates.len() == 1 {
+            return Ok(candidates.pop().unwrap());
+        }
+
+        let all_candidates = candidates
+            .iter()
+            .map(|c| self.describe_symbol(c))
+            .collect::<Vec<_>>();
+
+        // only select candidates, which adhere to the argument types
+        let mut c = self.filter_by_signature(candidates, arg_types)?;
+
+        if c.is_empty() {
+            return Err(CompilationError::NoCandidates {
+                symbol,
+                args: self.type_names(arg_types),
+                candidates: all_candidates,
+            });
+        }
+
+        if let Some(type_hint) = type_hint {
+            if c.len() == 1 {
+                return Ok(c.pop().unwrap());
+            }
+
+            let mut cn = Vec::new();
+            for candidate in c {
+                if self.candidate_return_type(&candidate, arg_types)? == type_hint {
+                    cn.push(candidate);
+                }
+            }
+
+            if cn.is_empty() {
+                return Err(CompilationError::NoCandidatesForTypeHint {
+                    symbol,
+                    type_hint: self.type_name(type_hint),
+                    candidates: all_candidates,
+                });
+            }
+
+            if cn.len() > 1 {
+                return Err(CompilationError::AmbiguousSymbol {
+                    symbol,
+                    candidates: cn.iter().map(|c| self.describe_symbol(c)).collect(),
+                    type_hint: Some(self.type_name(type_hint)),
+                });
+            }
+
+            return Ok(cn.pop().unwrap());
+        }
+
+        if c.len() > 1 {
+            return Err(CompilationError::AmbiguousSymbol {
+                symbol,
+                candidates: c.iter().map(|c| self.describe_symbol(c)).collect(),
+                type_hint: None,
+            });
+        }
+
+        Ok(c.pop().unwrap())
+    }
+
+    /// Type, that the candidate yields, when it gets called with the supplied arguments.
+    /// Uses the declared return type, if there is one.
+    /// Otherwise the candidate needs to be compiled.
+    fn candidate_return_type(
+        &'a self,
+        candidate: &Symbol,
+        arg_types: &[TypeId],
+    ) -> Result<TypeId, CompilationError> {
+        let symbol_id = match candidate {
+            Symbol::LocalVar { ty, .. } => return Ok(*ty),
+            Symbol::Global(symbol_id) => symbol_id,
+        };
+
+        let (mut lookup, item) = self.lookup(symbol_id);
+        if let BodyItem::Function(func) = item {
+            if let Some(ty) = &func.return_type {
+                // The return type might refer to type variables of the arguments
+                if let Some(bindings) = self.bind_arguments(func, arg_types, &lookup)? {
+                    lookup.generics = bindings;
+                    return self.resolve_type(ty, &lookup);
+                }
+            }
+        }
+
+        let (_, ty) = self.compile_symbol(symbol_id.clone(), arg_types)?;
+        Ok(ty)
+    }
+
+    /// Human readable description of a symbol, used for listing candidates.
+    /// e.g. `std(solar-lang).0.0.1.types.string.length(s: String) -> Int (string.sol)`
+    fn describe_symbol(&'a self, symbol: &Symbol) -> String {
+        match symbol {
+            Symbol::LocalVar { ty, .. } => format!("local variable of type {}", self.type_name(*ty)),
+            Symbol::Global(symbol_id) => self.describe_symbol_id(symbol_id),
+        }
+    }
+
+    pub(super) fn describe_symbol_id(&'a self, symbol_id: &SymbolId) -> String {
+        let (lookup, item) = self.lookup(symbol_id);
+        let module = symbol_id.0.join(".");
+
+        let signature = match item {
+            BodyItem::Function(func) => {
+                let args = func
+                    .args
+                    .iter()
+                    .map(|(ident, ty)| match ty {
+                        Some(ty) => format!("{}: {}", ident.value, util::type_path(ty).join(".")),
+                        None => ident.value.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                let ret = match &func.return_type {
+                    Some(ty) => format!(" -> {}", util::type_path(ty).join(".")),
+                    None => String::new(),
+                };
+
+                format!("{}({args}){ret}", func.name.value)
+            }
+            BodyItem::TypeDecl(ty) => format!("type {}", ty.name.value),
+            BodyItem::BuildinTypeDecl(ty) => format!("buildin_type {}", ty.name.value),
+            BodyItem::Let(var) => format!("let {}", var.identifier.value),
+            BodyItem::Test(test) => format!("test \"{}\"", test.description),
+        };
+
+        format!("{module}.{signature} ({})", lookup.file)
+    }
+
+    pub(super) fn type_names(&self, types: &[TypeId]) -> Vec<String> {
+        types.iter().map(|ty| self.type_name(*ty)).collect()
+    }
+}
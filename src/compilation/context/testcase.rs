@@ -0,0 +1,101 @@
+use solar_parser::ast::body::BodyItem;
+
+use super::CompilerContext;
+use crate::{
+    compilation::CompilationError,
+    diagnostics::Location,
+    id::{FunctionId, IdItem, IdModule, SymbolId, TypeId},
+    util::{self, Scope},
+};
+
+/// A test block inside the target project
+///
+/// e.g.
+/// test "greeting" = greet "Peter" == "Hello Peter"
+#[derive(Debug, Clone)]
+pub struct TestCase {
+    pub symbol_id: SymbolId,
+    pub description: String,
+    pub location: Option<Location>,
+}
+
+impl TestCase {
+    pub fn module(&self) -> &IdModule {
+        &self.symbol_id.0
+    }
+}
+
+/// Tests
+///
+/// Tests don't have names, so they can't be referenced by other code.
+/// Each one gets compiled into a function without arguments,
+/// that is run as its own entry point.
+/// A test passes, if it evaluates to true without runtime errors.
+impl<'a> CompilerContext<'a> {
+    /// All tests of the current target project.
+    /// Sorted by module, and by position within the module.
+    pub fn target_tests(&'a self) -> Vec<TestCase> {
+        let target = util::target_id();
+
+        let mut idmodules = self
+            .module_info
+            .keys()
+            .filter(|idmodule| idmodule.starts_with(&target))
+            .collect::<Vec<_>>();
+        idmodules.sort();
+
+        let mut tests = Vec::new();
+        for idmodule in idmodules {
+            let module = &self.module_info[idmodule];
+
+            for (idfile, fileinfo) in module.files.iter().enumerate() {
+                for (iditem, item) in fileinfo.ast.items.iter().enumerate() {
+                    let BodyItem::Test(test) = item else {
+                        continue;
+                    };
+
+                    tests.push(TestCase {
+                        symbol_id: (idmodule.clone(), idfile as u16, IdItem::Test(iditem as u16)),
+                        description: test.description.to_string(),
+                        location: fileinfo.locate(test.description),
+                    });
+                }
+            }
+        }
+
+        tests
+    }
+
+    /// Compiles a test into a function without arguments.
+    /// Its body needs to be of type Bool.
+    pub fn compile_test(&'a self, symbol_id: &SymbolId) -> Result<FunctionId, CompilationError> {
+        let (lookup, item) = self.lookup(symbol_id);
+        let BodyItem::Test(test) = item else {
+            unreachable!("test ids only reference tests");
+        };
+
+        let name = format!("test \"{}\"", test.description);
+        let file = lookup.file;
+        let located = lookup.clone();
+
+        let bool = self.buildin_types.bool as TypeId;
+
+        let (id, _) = self.derived_function((symbol_id.clone(), Vec::new()), &[], || {
+            let body =
+                self.compile_full_expression(&test.body, lookup, &mut Scope::new(), Some(bool))?;
+
+            if body.ty != bool {
+                return Err(CompilationError::TypeError {
+                    got: self.type_name(body.ty),
+                    wanted: self.type_name(bool),
+                    location: None,
+                });
+            }
+
+            Ok(body)
+        })
+        .map_err(|e| located.locate(e, test.description).in_function(&name, file))?;
+
+        Ok(id)
+    }
+}
@@ -0,0 +1,651 @@
+use solar_parser::ast::{self, body::BodyItem};
+
+use super::{generics::Bindings, CompilerContext, Lookup};
+use crate::{
+    compilation::CompilationError,
+    id::{FunctionId, IdItem, SymbolId, TypeId, SSID},
+    mir::{Instruction, StaticExpression, VariantCase},
+    types::{buildin::buildin_size, Type, REFERENCE_SIZE},
+    util::Scope,
+};
+
+/// Compiling user defined types
+/// and the functions derived from them.
+///
+/// e.g.
+/// type Wrapper a
+///   - value: a
+///
+/// derives the constructor `Wrapper(a) -> Wrapper a`
+/// and the field accessor `value(Wrapper a) -> a`
+///
+/// type Maybe a
+///   = Some a
+///   | None
+///
+/// derives the constructor `Some(a) -> Maybe a`
+/// and the constant `None`
+///
+/// Generic types are registered once per combination of type arguments,
+/// e.g. `Maybe Int` and `Maybe String`.
+impl<'a> CompilerContext<'a> {
+    /// Registers the instantiation of the type declaration referenced by the symbol
+    /// (or one of it's derived methods) in the type store,
+    /// if it's not registered yet.
+    pub(super) fn compile_type(
+        &'a self,
+        symbol_id: &SymbolId,
+        generics: Vec<TypeId>,
+    ) -> Result<TypeId, CompilationError> {
+        let ssid: SSID = (type_symbol(symbol_id), generics);
+
+        let failed_before = match self
+            .types
+            .read()
+            .expect("aquire readlock for types")
+            .get_by_key(&ssid)
+        {
+            // A previous attempt failed, so the error gets reported again.
+            Some((id, _))
+                if self
+                    .failed_types
+                    .read()
+                    .expect("aquire readlock")
+                    .contains(&id) =>
+            {
+                Some(id)
+            }
+            // Either laid out already, or a recursive reference from one of its fields.
+            Some((id, _)) => return Ok(id),
+            None => None,
+        };
+
+        let (mut lookup, item) = self.lookup(&ssid.0);
+        let name = match item {
+            BodyItem::TypeDecl(decl) => decl.name.value,
+            BodyItem::BuildinTypeDecl(decl) => decl.name.value,
+            _ => unreachable!("types are always declared by type declarations"),
+        };
+
+        let parameters = type_parameters(item);
+        if parameters.len() != ssid.1.len() {
+            return Err(CompilationError::TypeArgumentCount {
+                ty: name.to_string(),
+                expected: parameters.len(),
+                got: ssid.1.len(),
+            });
+        }
+
+        let instance = self.instance_name(name, &ssid.1);
+
+        let BodyItem::TypeDecl(decl) = item else {
+            // generic buildin types, e.g. `Array a`
+            let ty = Type::buildin(instance, &ssid, buildin_size(name));
+            return Ok(self.types.write().expect("store type").insert(ssid, ty));
+        };
+
+        // Reserve the type first, so that fields may refer to it.
+        let id = match failed_before {
+            Some(id) => {
+                self.failed_types.write().expect("restart type").remove(&id);
+                id
+            }
+            None => self
+                .types
+                .write()
+                .expect("reserve type")
+                .insert(ssid.clone(), Type::reserved(&instance, &ssid)),
+        };
+
+        // The fields may refer to the type parameters
+        lookup.generics = parameters.into_iter().zip(ssid.1.iter().copied()).collect();
+
+        // Until it is laid out, fields referring to the type (even indirectly)
+        // see only the placeholder.
+        self.laying_out.write().expect("mark type as in progress").insert(id);
+        let layout = self.type_layout(decl, &instance, &ssid, &lookup);
+        self.laying_out.write().expect("mark type as done").remove(&id);
+
+        let ty = match layout {
+            Ok(ty) => ty,
+            Err(e) => {
+                // The placeholder must not be mistaken for the laid out type.
+                self.failed_types
+                    .write()
+                    .expect("mark type as failed")
+                    .insert(id);
+
+                if ssid.1.is_empty() {
+                    return Err(e);
+                }
+                return Err(self.instantiation_error(&ssid.0, &ssid.1, e));
+            }
+        };
+
+        self.types.write().expect("store type").set_by_index(id, ty);
+
+        Ok(id)
+    }
+
+    /// Resolves the types of the fields or variants of a type declaration.
+    fn type_layout(
+        &'a self,
+        decl: &ast::TypeDecl,
+        instance: &str,
+        ssid: &SSID,
+        lookup: &Lookup,
+    ) -> Result<Type, CompilationError> {
+        let ty = match &decl.fields {
+            ast::EnumOrStructFields::StructFields(fields) => {
+                let mut layout = Vec::with_capacity(fields.len());
+                for field in fields {
+                    let field_ty = self.resolve_type(&field.ty, lookup)?;
+                    let size = self.field_size(field_ty);
+
+                    layout.push((field.name.value.to_string(), field_ty, size));
+                }
+
+                Type::new_struct(instance, ssid, layout)
+            }
+            ast::EnumOrStructFields::EnumFields(variants) => {
+                let mut layout = Vec::with_capacity(variants.len());
+                for variant in variants {
+                    let (payload, size) = match &variant.ty {
+                        Some(ty) => {
+                            let payload = self.resolve_type(ty, lookup)?;
+                            (Some(payload), self.field_size(payload))
+                        }
+                        None => (None, 0),
+                    };
+
+                    layout.push((variant.name.value.to_string(), payload, size));
+                }
+
+                Type::new_enum(instance, ssid, layout)
+            }
+        };
+
+        Ok(ty)
+    }
+
+    /// Size of a field or payload of the given type.
+    /// Recursive types, including mutually recursive ones, are stored by reference,
+    /// because the size of a type isn't known, while it is laid out.
+    fn field_size(&self, ty: TypeId) -> u32 {
+        let in_progress = self
+            .laying_out
+            .read()
+            .expect("aquire readlock for types in progress")
+            .contains(&ty);
+
+        if in_progress {
+            REFERENCE_SIZE
+        } else {
+            self.type_size(ty)
+        }
+    }
+
+    /// Compiles the constructor of a type,
+    /// which takes all fields in order of their declaration.
+    /// The type arguments are derived from the fields, e.g. `Wrapper 5` is a `Wrapper Int`.
+    pub(super) fn compile_constructor(
+        &'a self,
+        symbol_id: &SymbolId,
+        args: &[TypeId],
+    ) -> Result<(FunctionId, TypeId), CompilationError> {
+        let (lookup, item) = self.lookup(symbol_id);
+        let BodyItem::TypeDecl(decl) = item else {
+            unreachable!("constructors are derived from type declarations");
+        };
+        let name = decl.name.value;
+
+        let ast::EnumOrStructFields::StructFields(declared) = &decl.fields else {
+            return Err(CompilationError::ConstructingEnum(name.to_string()));
+        };
+
+        if declared.len() != args.len() {
+            return Err(CompilationError::ArgumentCount {
+                function: name.to_string(),
+                expected: declared.len(),
+                got: args.len(),
+            });
+        }
+
+        let parameters = type_parameters(item);
+        let is_parameter = |n: &str| parameters.iter().any(|p| p == n);
+
+        let mut bindings = Bindings::new();
+        for (field, arg) in declared.iter().zip(args) {
+            // mismatches are reported below, once the field types are known
+            self.unify(&field.ty, *arg, &lookup, &is_parameter, &mut bindings)?;
+        }
+
+        let generics = bound_generics(name, &parameters, &bindings)?;
+        let ty = self.compile_type(symbol_id, generics)?;
+        let fields = self.type_fields(ty);
+
+        for ((_, _, field_ty), arg) in fields.iter().zip(args) {
+            if field_ty != arg {
+                return Err(CompilationError::TypeError {
+                    got: self.type_name(*arg),
+                    wanted: self.type_name(*field_ty),
+                    location: None,
+                }
+                .in_function(name, lookup.file));
+            }
+        }
+
+        self.derived_function((symbol_id.clone(), args.to_vec()), args, || {
+            let fields = args
+                .iter()
+                .enumerate()
+                .map(|(addr, ty)| Instruction::GetLocalVar(addr).expr(*ty))
+                .collect();
+
+            Ok(Instruction::NewStruct { fields }.expr(ty))
+        })
+    }
+
+    /// Compiles methods derived from fields of a type,
+    /// e.g. the accessor `value(Wrapper a) -> a`
+    /// The type hint is used by constants of generic enums, e.g. `None`.
+    pub(super) fn compile_derived_method(
+        &'a self,
+        symbol_id: &SymbolId,
+        args: &[TypeId],
+        type_hint: Option<TypeId>,
+    ) -> Result<(FunctionId, TypeId), CompilationError> {
+        let IdItem::Method(_, field) = symbol_id.2 else {
+            unreachable!("derived methods are referenced by IdItem::Method");
+        };
+
+        let (lookup, item) = self.lookup(symbol_id);
+        let BodyItem::TypeDecl(decl) = item else {
+            unreachable!("methods are derived from type declarations");
+        };
+
+        let ast::EnumOrStructFields::StructFields(fields) = &decl.fields else {
+            return self.compile_variant(symbol_id, field, args, type_hint);
+        };
+        let name = fields[field as usize].name.value;
+
+        if args.len() != 1 {
+            return Err(CompilationError::ArgumentCount {
+                function: name.to_string(),
+                expected: 1,
+                got: args.len(),
+            });
+        }
+
+        // The accessor is instantiated for the type arguments of the struct,
+        // e.g. `value(Wrapper Int) -> Int`
+        let (declaration, generics) = self.type_ssid(args[0]);
+        if declaration != type_symbol(symbol_id) {
+            return Err(CompilationError::TypeError {
+                got: self.type_name(args[0]),
+                wanted: decl.name.value.to_string(),
+                location: None,
+            }
+            .in_function(name, lookup.file));
+        }
+
+        let ty = self.compile_type(symbol_id, generics)?;
+        let field_ty = self.type_fields(ty)[field as usize].2;
+
+        self.derived_function((symbol_id.clone(), args.to_vec()), args, || {
+            let value = Instruction::GetLocalVar(0).expr(ty);
+            Ok(Instruction::GetField { value, field }.expr(field_ty))
+        })
+    }
+
+    /// Checks, whether a derived method can be called with arguments of the supplied types.
+    pub(super) fn method_matches(
+        &'a self,
+        symbol_id: &SymbolId,
+        arg_types: &[TypeId],
+    ) -> Result<bool, CompilationError> {
+        let IdItem::Method(_, field) = symbol_id.2 else {
+            unreachable!("derived methods are referenced by IdItem::Method");
+        };
+
+        let (lookup, item) = self.lookup(symbol_id);
+        let BodyItem::TypeDecl(decl) = item else {
+            unreachable!("methods are derived from type declarations");
+        };
+
+        let variants = match &decl.fields {
+            ast::EnumOrStructFields::StructFields(_) => {
+                // field accessors take exactly the struct, regardless of it's type arguments
+                let matches = match arg_types {
+                    [arg] => self.type_ssid(*arg).0 == type_symbol(symbol_id),
+                    _ => false,
+                };
+
+                return Ok(matches);
+            }
+            ast::EnumOrStructFields::EnumFields(variants) => variants,
+        };
+
+        // variant constructors take the payload, if there is any
+        match (&variants[field as usize].ty, arg_types) {
+            (Some(payload), [arg]) => {
+                let parameters = type_parameters(item);
+                let is_parameter = |n: &str| parameters.iter().any(|p| p == n);
+
+                self.unify(payload, *arg, &lookup, &is_parameter, &mut Bindings::new())
+            }
+            (None, []) => Ok(true),
+            _ => Ok(false),
+        }
+    }
+
+    /// Compiles the constructor of an enum variant.
+    /// e.g. `Some(a) -> Maybe a`
+    /// Variants without payload are constants, e.g. `None`.
+    /// Their type arguments can only be derived from the type hint.
+    fn compile_variant(
+        &'a self,
+        symbol_id: &SymbolId,
+        variant: u16,
+        args: &[TypeId],
+        type_hint: Option<TypeId>,
+    ) -> Result<(FunctionId, TypeId), CompilationError> {
+        let (lookup, item) = self.lookup(symbol_id);
+        let BodyItem::TypeDecl(decl) = item else {
+            unreachable!("variants are derived from type declarations");
+        };
+        let ast::EnumOrStructFields::EnumFields(variants) = &decl.fields else {
+            unreachable!("variants are derived from enum declarations");
+        };
+
+        let declared = &variants[variant as usize];
+        let name = declared.name.value;
+
+        let expected = declared.ty.iter().count();
+        if expected != args.len() {
+            return Err(CompilationError::ArgumentCount {
+                function: name.to_string(),
+                expected,
+                got: args.len(),
+            });
+        }
+
+        let parameters = type_parameters(item);
+        let is_parameter = |n: &str| parameters.iter().any(|p| p == n);
+
+        let mut bindings = Bindings::new();
+        if let (Some(payload), [arg]) = (&declared.ty, args) {
+            // mismatches are reported below, once the payload type is known
+            self.unify(payload, *arg, &lookup, &is_parameter, &mut bindings)?;
+        }
+
+        if let Some(type_hint) = type_hint {
+            let (declaration, generics) = self.type_ssid(type_hint);
+            if declaration == type_symbol(symbol_id) {
+                for (parameter, ty) in parameters.iter().zip(generics) {
+                    if !bindings.iter().any(|(n, _)| n == parameter) {
+                        bindings.push((parameter.clone(), ty));
+                    }
+                }
+            }
+        }
+
+        let generics = bound_generics(decl.name.value, &parameters, &bindings)?;
+        let ty = self.compile_type(symbol_id, generics.clone())?;
+        let payload = self.type_variants(ty)[variant as usize].1;
+
+        if let (Some(payload), [arg]) = (payload, args) {
+            if *arg != payload {
+                return Err(CompilationError::TypeError {
+                    got: self.type_name(*arg),
+                    wanted: self.type_name(payload),
+                    location: None,
+                }
+                .in_function(name, lookup.file));
+            }
+        }
+
+        // Constants don't take arguments,
+        // so the instantiation is identified by the type arguments.
+        self.derived_function((symbol_id.clone(), generics), args, || {
+            let payload = payload.map(|payload| Instruction::GetLocalVar(0).expr(payload));
+
+            Ok(Instruction::NewVariant { variant, payload }.expr(ty))
+        })
+    }
+
+    /// Compiles a match on the variants of an enum value.
+    /// e.g.
+    /// match maybe_name
+    ///   | Some name => name
+    ///   | None => "stranger"
+    pub(super) fn compile_match(
+        &'a self,
+        expr: &ast::expr::MatchExpression,
+        lookup: Lookup,
+        scope: &mut Scope,
+        type_hint: Option<TypeId>,
+    ) -> Result<StaticExpression, CompilationError> {
+        let value = self.compile_full_expression(&expr.value, lookup.clone(), scope, None)?;
+
+        let variants = self.type_variants(value.ty);
+        if variants.is_empty() {
+            return Err(CompilationError::TypeError {
+                got: self.type_name(value.ty),
+                wanted: "enum".to_string(),
+                location: None,
+            });
+        }
+
+        let mut cases: Vec<VariantCase> = Vec::with_capacity(expr.cases.len());
+        let mut default = None;
+        let mut ty = type_hint;
+
+        for case in &expr.cases {
+            // Every case gets it's own scope, only one of them gets evaluated.
+            let mut case_scope = scope.clone();
+
+            let variant_name = case.variant.value;
+
+            let variant = if variant_name == "_" {
+                None
+            } else {
+                let Some(variant) = variants.iter().position(|(n, _)| n == variant_name) else {
+                    let e = CompilationError::UnknownVariant {
+                        ty: self.type_name(value.ty),
+                        variant: variant_name.to_string(),
+                    };
+                    return Err(lookup.locate(e, case.variant.span));
+                };
+
+                Some(variant)
+            };
+
+            let duplicate = match variant {
+                Some(variant) => cases.iter().any(|c| c.variant as usize == variant),
+                None => default.is_some(),
+            };
+            if duplicate {
+                let e = CompilationError::DuplicateCase {
+                    ty: self.type_name(value.ty),
+                    variant: variant_name.to_string(),
+                };
+                return Err(lookup.locate(e, case.variant.span));
+            }
+
+            let binding = match (&case.binding, variant.map(|v| variants[v].1)) {
+                (Some(ident), Some(Some(payload))) => Some(case_scope.push(ident.value, payload)),
+                // `_` matches variants with and without payload alike
+                (Some(_), None) => {
+                    let e = CompilationError::DefaultCaseBinding(self.type_name(value.ty));
+                    return Err(lookup.locate(e, case.variant.span));
+                }
+                (Some(_), Some(None)) => {
+                    return Err(CompilationError::ArgumentCount {
+                        function: variant_name.to_string(),
+                        expected: 0,
+                        got: 1,
+                    })
+                }
+                (None, _) => None,
+            };
+
+            let body =
+                self.compile_full_expression(&case.body, lookup.clone(), &mut case_scope, ty)?;
+
+            match ty {
+                Some(ty) if ty != body.ty => {
+                    return Err(CompilationError::TypeError {
+                        got: self.type_name(body.ty),
+                        wanted: self.type_name(ty),
+                        location: None,
+                    });
+                }
+                _ => ty = Some(body.ty),
+            }
+
+            match variant {
+                Some(variant) => cases.push(VariantCase {
+                    variant: variant as u16,
+                    binding,
+                    body,
+                }),
+                None => default = Some(body),
+            }
+        }
+
+        if default.is_none() {
+            let missing = variants
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !cases.iter().any(|c| c.variant as usize == *i))
+                .map(|(_, (name, _))| name.clone())
+                .collect::<Vec<_>>();
+
+            if !missing.is_empty() {
+                return Err(CompilationError::NonExhaustiveMatch {
+                    ty: self.type_name(value.ty),
+                    missing,
+                });
+            }
+        }
+
+        let ty = ty.expect("match to have at least one case");
+
+        Ok(Instruction::MatchVariant {
+            value,
+            cases,
+            default,
+        }
+        .expr(ty))
+    }
+
+    /// Stores a function generated by the compiler, unless it has been generated before.
+    pub(super) fn derived_function(
+        &'a self,
+        ssid: SSID,
+        args: &[TypeId],
+        body: impl FnOnce() -> Result<StaticExpression, CompilationError>,
+    ) -> Result<(FunctionId, TypeId), CompilationError> {
+        let reserved = match self
+            .functions
+            .read()
+            .expect("aquire readlock for functions")
+            .get_by_key(&ssid)
+        {
+            Some((id, super::FunctionInfo::Complete { body, .. })) => return Ok((id, body.ty)),
+            Some((id, super::FunctionInfo::Cached { return_type, .. })) => {
+                return Ok((id, *return_type))
+            }
+            // reserved for bytecode of the cache, that couldn't be linked
+            Some((id, _)) => Some(id),
+            None => None,
+        };
+
+        let body = body()?;
+        let ty = body.ty;
+        let args = args.to_vec();
+
+        let mut functions = self.functions.write().expect("store derived function");
+        let id = reserved.unwrap_or_else(|| functions.reserve(ssid, Some(ty)));
+        functions.update_complete_function(id, args, body);
+
+        Ok((id, ty))
+    }
+
+    fn type_fields(&self, ty: TypeId) -> Vec<(String, u32, TypeId)> {
+        self.types
+            .read()
+            .expect("aquire readlock for types")
+            .get_by_index(ty)
+            .expect("find type in type store")
+            .fields()
+            .to_vec()
+    }
+
+    pub(super) fn type_variants(&self, ty: TypeId) -> Vec<(String, Option<TypeId>)> {
+        self.types
+            .read()
+            .expect("aquire readlock for types")
+            .get_by_index(ty)
+            .expect("find type in type store")
+            .variants()
+            .to_vec()
+    }
+
+    fn type_size(&self, ty: TypeId) -> u32 {
+        self.types
+            .read()
+            .expect("aquire readlock for types")
+            .get_by_index(ty)
+            .expect("find type in type store")
+            .size()
+    }
+}
+
+/// The symbol of the type declaration,
+/// which might be referenced by one of it's derived methods.
+fn type_symbol((module, file, item): &SymbolId) -> SymbolId {
+    let item = match item {
+        IdItem::Method(ty, _) => IdItem::Type(*ty),
+        item => *item,
+    };
+
+    (module.clone(), *file, item)
+}
+
+/// Names of the type parameters of a type declaration, e.g. `a` in `type Maybe a`
+fn type_parameters(item: &BodyItem) -> Vec<String> {
+    let symbols = match item {
+        BodyItem::TypeDecl(decl) => &decl.generic_symbols,
+        BodyItem::BuildinTypeDecl(decl) => &decl.generic_symbols,
+        _ => return Vec::new(),
+    };
+
+    symbols
+        .iter()
+        .flat_map(|generics| generics.symbols.iter())
+        .map(|symbol| symbol.value.to_string())
+        .collect()
+}
+
+/// Type arguments for the parameters of a type, in order of their declaration.
+fn bound_generics(
+    ty: &str,
+    parameters: &[String],
+    bindings: &Bindings,
+) -> Result<Vec<TypeId>, CompilationError> {
+    parameters
+        .iter()
+        .map(|parameter| {
+            bindings
+                .iter()
+                .find(|(name, _)| name == parameter)
+                .map(|(_, ty)| *ty)
+                .ok_or_else(|| CompilationError::UninferredTypeParameter {
+                    ty: ty.to_string(),
+                    parameter: parameter.clone(),
+                })
+        })
+        .collect()
+}
@@ -1,4 +1,8 @@
-use crate::project::FindError;
+use crate::{
+    diagnostics::Location,
+    id::{FunctionId, GlobalId},
+    project::FindError,
+};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -12,12 +16,122 @@ pub enum CompilationError {
     TypeError {
         got: String,
         wanted: String,
+        /// Function and file, the type error occurred in.
+        /// Gets filled in, when the error leaves the function.
+        location: Option<(String, String)>,
+    },
+    /// A function was called with the wrong number of arguments
+    ArgumentCount {
+        function: String,
+        expected: usize,
+        got: usize,
     },
     /// Variables musn't be called
     CallingVariable {
         identifer: String,
         file: String,
     },
+    /// None of the candidates for a symbol can be called with the supplied arguments
+    NoCandidates {
+        symbol: String,
+        args: Vec<String>,
+        candidates: Vec<String>,
+    },
+    /// None of the candidates for a symbol returns the expected type
+    NoCandidatesForTypeHint {
+        symbol: String,
+        type_hint: String,
+        candidates: Vec<String>,
+    },
+    /// Multiple candidates for a symbol fit equally well
+    AmbiguousSymbol {
+        symbol: String,
+        candidates: Vec<String>,
+        type_hint: Option<String>,
+    },
+    /// Buildin types have no constructor, that could be called
+    CallingBuildinType(String),
+    /// Enums are constructed through their variants
+    ConstructingEnum(String),
+    /// A match refers to a variant, that the enum doesn't have
+    UnknownVariant { ty: String, variant: String },
+    /// A match handles the same variant (or `_`) more than once
+    DuplicateCase { ty: String, variant: String },
+    /// The `_` case of a match binds a variable, but there is no single payload it could hold
+    DefaultCaseBinding(String),
+    /// A match doesn't handle all variants of the enum
+    NonExhaustiveMatch { ty: String, missing: Vec<String> },
+    /// A type annotation that doesn't refer to any known type
+    UnknownType(String),
+    /// A function got called recursively,
+    /// before it's return type could be determined.
+    UnresolvedRecursion {
+        function: String,
+        id: FunctionId,
+    },
+    /// A generic type was supplied the wrong number of type arguments
+    TypeArgumentCount {
+        ty: String,
+        expected: usize,
+        got: usize,
+    },
+    /// A type parameter couldn't be derived from the arguments or the type hint
+    UninferredTypeParameter { ty: String, parameter: String },
+    /// Instantiating a generic function or type with concrete types failed.
+    /// Points at the generic definition.
+    Instantiation {
+        definition: String,
+        types: Vec<String>,
+        error: Box<CompilationError>,
+    },
+    /// Global variables, that depend on each other.
+    /// The first and the last one are the same.
+    CyclicGlobals(Vec<String>),
+    /// The value of a global variable reads input or prints output,
+    /// so it can't be evaluated during compilation.
+    ImpureGlobal { global: String, instruction: String },
+    /// Evaluating the value of a global variable during compilation failed
+    GlobalEvaluation { global: String, error: RuntimeError },
+    /// The error occurred at this location in the source code
+    Located {
+        location: Location,
+        error: Box<CompilationError>,
+    },
+}
+
+impl CompilationError {
+    /// Attaches the function and file to type errors, that don't have a location yet.
+    pub fn in_function(self, function: &str, file: &str) -> Self {
+        match self {
+            Self::TypeError {
+                got,
+                wanted,
+                location: None,
+            } => Self::TypeError {
+                got,
+                wanted,
+                location: Some((function.to_string(), file.to_string())),
+            },
+            Self::Located { location, error } => Self::Located {
+                location,
+                error: Box::new(error.in_function(function, file)),
+            },
+            e => e,
+        }
+    }
+
+    /// Attaches the location in the source code, unless the error has been located before.
+    /// The innermost location is the most precise one.
+    pub fn at(self, location: Option<Location>) -> Self {
+        match (self, location) {
+            (e @ Self::Located { .. }, _) => e,
+            (e, Some(location)) => Self::Located {
+                location,
+                error: Box::new(e),
+            },
+            (e, None) => e,
+        }
+    }
 }
 
 impl std::fmt::Display for CompilationError {
@@ -30,13 +144,174 @@ impl std::fmt::Display for CompilationError {
                 write!(f, "only buildin methods are allowed to start with buildin_ or Buildin_.\n Found {found}.")
             }
 
-            Self::TypeError { got, wanted } => {
-                write!(f, "Wrong type supplied. Expected {wanted}, got {got}")
+            Self::TypeError {
+                got,
+                wanted,
+                location,
+            } => {
+                write!(f, "Wrong type supplied. Expected {wanted}, got {got}")?;
+                if let Some((function, file)) = location {
+                    write!(f, " in function {function} ({file})")?;
+                }
+                Ok(())
+            }
+
+            Self::ArgumentCount {
+                function,
+                expected,
+                got,
+            } => {
+                write!(f, "function {function} takes {expected} arguments, but {got} were supplied")
             }
 
             Self::CallingVariable { identifer, file } => {
                 write!(f, "tried to call variable {identifer} in {file}. Don't supply arguments to variables, it will be interpreted as a function call.")
             }
+
+            Self::NoCandidates {
+                symbol,
+                args,
+                candidates,
+            } => {
+                if candidates.is_empty() {
+                    return write!(f, "{symbol} not found");
+                }
+
+                write!(f, "no candidate for {symbol} accepts arguments ({}). Found:", args.join(", "))?;
+                write_candidates(f, candidates)
+            }
+
+            Self::NoCandidatesForTypeHint {
+                symbol,
+                type_hint,
+                candidates,
+            } => {
+                write!(f, "no candidate for {symbol} returns {type_hint}. Found:")?;
+                write_candidates(f, candidates)
+            }
+
+            Self::AmbiguousSymbol {
+                symbol,
+                candidates,
+                type_hint: Some(_),
+            } => {
+                write!(f, "Multiple declarations found for {symbol}. Remove import or delete one. Found:")?;
+                write_candidates(f, candidates)
+            }
+
+            Self::AmbiguousSymbol {
+                symbol,
+                candidates,
+                type_hint: None,
+            } => {
+                write!(f, "Please specify type hint for {symbol}. Found multiple candidates:")?;
+                write_candidates(f, candidates)
+            }
+
+            Self::CallingBuildinType(name) => {
+                write!(f, "buildin type {name} can't be constructed. Use literals instead.")
+            }
+
+            Self::ConstructingEnum(name) => {
+                write!(f, "enum {name} can't be constructed directly. Use one of it's variants instead.")
+            }
+
+            Self::UnknownVariant { ty, variant } => {
+                write!(f, "type {ty} has no variant {variant}")
+            }
+
+            Self::DuplicateCase { ty, variant } => {
+                write!(f, "match on {ty} handles {variant} more than once")
+            }
+
+            Self::DefaultCaseBinding(ty) => {
+                write!(f, "the `_` case of a match on {ty} can't bind a variable, because it covers all remaining variants. Match the variants with payload separately.")
+            }
+
+            Self::NonExhaustiveMatch { ty, missing } => {
+                write!(f, "match on {ty} doesn't handle variants {}. Add the missing cases or a `_` case.", missing.join(", "))
+            }
+
+            Self::UnknownType(name) => write!(f, "type {name} not found"),
+
+            Self::UnresolvedRecursion { function, .. } => {
+                write!(f, "can't infer the return type of recursive function {function}. Consider declaring it, e.g. `fun {function}(..) -> Int`")
+            }
+
+            Self::TypeArgumentCount { ty, expected, got } => {
+                write!(f, "type {ty} takes {expected} type arguments, but {got} were supplied")
+            }
+
+            Self::UninferredTypeParameter { ty, parameter } => {
+                write!(f, "can't infer type parameter {parameter} of {ty}. Consider adding a type annotation.")
+            }
+
+            Self::Instantiation {
+                definition,
+                types,
+                error,
+            } => {
+                write!(f, "{error}\n  while instantiating {definition} with ({})", types.join(", "))
+            }
+
+            Self::CyclicGlobals(globals) => {
+                write!(f, "global variables depend on each other: {}", globals.join(" -> "))
+            }
+
+            Self::ImpureGlobal { global, instruction } => {
+                write!(f, "global variable {global} can't be evaluated during compilation, because it performs IO ({instruction}). Compute the value inside a function instead.")
+            }
+
+            Self::GlobalEvaluation { global, error } => {
+                write!(f, "evaluating global variable {global} failed: {error}")
+            }
+
+            Self::Located { location, error } => write!(f, "{error}\n{location}"),
+        }
+    }
+}
+
+fn write_candidates(f: &mut std::fmt::Formatter<'_>, candidates: &[String]) -> std::fmt::Result {
+    for c in candidates {
+        write!(f, "\n    {c}")?;
+    }
+
+    Ok(())
+}
+
+/// Errors, that can occur while evaluating compiled code.
+#[derive(Debug, Error)]
+pub enum RuntimeError {
+    Io(#[from] std::io::Error),
+    /// The function has been reserved, but it's body was never compiled.
+    FunctionNotCompiled(FunctionId),
+    /// The global variable has been referenced, but it's value was never computed.
+    GlobalNotEvaluated(GlobalId),
+    /// None of the cases of a match handle the variant
+    UnmatchedVariant(u16),
+    /// A value didn't have the type, that got checked during compilation.
+    UnexpectedValue {
+        instruction: String,
+        got: &'static str,
+    },
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => e.fmt(f),
+            Self::FunctionNotCompiled(id) => {
+                write!(f, "tried to call function #{id}, which has not been compiled")
+            }
+            Self::GlobalNotEvaluated(id) => {
+                write!(f, "tried to read global variable #{id}, which has not been evaluated")
+            }
+            Self::UnmatchedVariant(variant) => {
+                write!(f, "no case matches variant #{variant}")
+            }
+            Self::UnexpectedValue { instruction, got } => {
+                write!(f, "unexpected value of type {got} in instruction {instruction}")
+            }
         }
     }
 }
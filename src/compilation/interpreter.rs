@@ -1,4 +1,12 @@
-use std::io;
+use std::io::{self, Read, Write};
+
+use super::{FunctionInfo, FunctionStore, GlobalStore, RuntimeError};
+use crate::{
+    bytecode::{Program, Vm},
+    id::FunctionId,
+    mir::{CustomInstructionCode, Instruction, StaticExpression},
+    value::{Int, Value},
+};
 
 pub struct InterpreterContext {
     writer: Box<dyn io::Write>,
@@ -16,6 +24,33 @@ impl std::default::Default for InterpreterContext {
     }
 }
 
+impl InterpreterContext {
+    /// Reads a single line, without the trailing line break.
+    /// Reads byte by byte, so that no input after the line gets consumed.
+    pub fn read_line(&mut self) -> io::Result<String> {
+        let mut line = Vec::new();
+        let mut byte = [0u8];
+
+        loop {
+            if self.reader.read(&mut byte)? == 0 {
+                break;
+            }
+
+            if byte[0] == b'\n' {
+                break;
+            }
+
+            line.push(byte[0]);
+        }
+
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+
+        String::from_utf8(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
 impl io::Write for InterpreterContext {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         self.writer.write(buf)
@@ -31,3 +66,271 @@ impl io::Read for InterpreterContext {
         self.reader.read(buf)
     }
 }
+
+/// Tree walking evaluator of compiled functions.
+/// Every function call gets its own frame of local variables.
+/// The arguments occupy the first slots, followed by the let bindings,
+/// just as they have been pushed into the [`crate::util::Scope`] during compilation.
+pub struct Interpreter<'a> {
+    functions: &'a FunctionStore,
+    globals: &'a GlobalStore,
+    io: &'a mut InterpreterContext,
+    /// Bytecode of the functions loaded from the cache, translated once they are called.
+    program: Option<Program>,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(
+        functions: &'a FunctionStore,
+        globals: &'a GlobalStore,
+        io: &'a mut InterpreterContext,
+    ) -> Self {
+        Interpreter {
+            functions,
+            globals,
+            io,
+            program: None,
+        }
+    }
+
+    /// Calls a compiled function and returns the resulting value.
+    /// Functions loaded from the cache only have bytecode, so they are run on the [`Vm`].
+    pub fn call(&mut self, func: FunctionId, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        let functions = self.functions;
+        let body = match functions.get_by_index(func) {
+            Some(FunctionInfo::Complete { body, .. }) => body,
+            Some(FunctionInfo::Cached { .. }) => {
+                let globals = self.globals;
+                let program = self
+                    .program
+                    .get_or_insert_with(|| Program::compile(functions, globals));
+                return Vm::new(program, &mut *self.io).call(func, args);
+            }
+            _ => return Err(RuntimeError::FunctionNotCompiled(func)),
+        };
+
+        let mut frame = args;
+        self.eval(body, &mut frame)
+    }
+
+    /// Evaluates an expression, that doesn't refer to any local variables.
+    /// e.g. the value of a global variable
+    pub fn evaluate(&mut self, expr: &StaticExpression) -> Result<Value, RuntimeError> {
+        self.eval(expr, &mut Vec::new())
+    }
+
+    /// Evaluates an expression with the given local variables.
+    /// Let bindings inside the expression are added to the frame.
+    pub fn evaluate_in(
+        &mut self,
+        expr: &StaticExpression,
+        frame: &mut Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        self.eval(expr, frame)
+    }
+
+    fn eval(
+        &mut self,
+        expr: &StaticExpression,
+        frame: &mut Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        match expr.instr.as_ref() {
+            Instruction::Const(value) => Ok(value.clone()),
+            Instruction::GetLocalVar(addr) => Ok(frame[*addr].clone()),
+            Instruction::GetGlobalVar(id) => self
+                .globals
+                .value(*id)
+                .cloned()
+                .ok_or(RuntimeError::GlobalNotEvaluated(*id)),
+            Instruction::NewLocalVar {
+                var_index,
+                var_value,
+                body,
+            } => {
+                let value = self.eval(var_value, frame)?;
+
+                let index = *var_index as usize;
+                if frame.len() <= index {
+                    frame.resize(index + 1, Value::Void);
+                }
+                frame[index] = value;
+
+                self.eval(body, frame)
+            }
+            Instruction::FunctionCall { func, args } => {
+                let args = self.eval_all(args, frame)?;
+                self.call(*func, args)
+            }
+            Instruction::IfExpr {
+                condition,
+                case_true,
+                case_false,
+            } => match self.eval(condition, frame)? {
+                Value::Bool(true) => self.eval(case_true, frame),
+                Value::Bool(false) => self.eval(case_false, frame),
+                value => Err(RuntimeError::UnexpectedValue {
+                    instruction: "if".to_string(),
+                    got: value.type_as_str(),
+                }),
+            },
+            Instruction::Custom { code, args } => {
+                let args = self.eval_all(args, frame)?;
+                run_custom(self.io, code, args)
+            }
+            Instruction::NewStruct { fields } => {
+                let fields = self.eval_all(fields, frame)?;
+                Ok(Value::Struct {
+                    ty: expr.ty,
+                    fields,
+                })
+            }
+            Instruction::NewVariant { variant, payload } => {
+                let payload = match payload {
+                    Some(payload) => Some(Box::new(self.eval(payload, frame)?)),
+                    None => None,
+                };
+
+                Ok(Value::Enum {
+                    ty: expr.ty,
+                    variant: *variant,
+                    payload,
+                })
+            }
+            Instruction::MatchVariant {
+                value,
+                cases,
+                default,
+            } => {
+                let (variant, payload) = match self.eval(value, frame)? {
+                    Value::Enum {
+                        variant, payload, ..
+                    } => (variant, payload),
+                    value => {
+                        return Err(RuntimeError::UnexpectedValue {
+                            instruction: "match".to_string(),
+                            got: value.type_as_str(),
+                        })
+                    }
+                };
+
+                let Some(case) = cases.iter().find(|c| c.variant == variant) else {
+                    return match default {
+                        Some(default) => self.eval(default, frame),
+                        None => Err(RuntimeError::UnmatchedVariant(variant)),
+                    };
+                };
+
+                if let (Some(index), Some(payload)) = (case.binding, payload) {
+                    let index = index as usize;
+                    if frame.len() <= index {
+                        frame.resize(index + 1, Value::Void);
+                    }
+                    frame[index] = *payload;
+                }
+
+                self.eval(&case.body, frame)
+            }
+            Instruction::GetField { value, field } => match self.eval(value, frame)? {
+                Value::Struct { mut fields, .. } => Ok(fields.swap_remove(*field as usize)),
+                value => Err(RuntimeError::UnexpectedValue {
+                    instruction: "field access".to_string(),
+                    got: value.type_as_str(),
+                }),
+            },
+        }
+    }
+
+    fn eval_all(
+        &mut self,
+        exprs: &[StaticExpression],
+        frame: &mut Vec<Value>,
+    ) -> Result<Vec<Value>, RuntimeError> {
+        exprs.iter().map(|e| self.eval(e, frame)).collect()
+    }
+}
+
+/// Executes a buildin instruction with its evaluated arguments.
+/// Shared by all backends, see [`crate::bytecode`].
+pub(crate) fn run_custom(
+    io: &mut InterpreterContext,
+    code: &CustomInstructionCode,
+    args: Vec<Value>,
+) -> Result<Value, RuntimeError> {
+    match code {
+        CustomInstructionCode::StrConcat => {
+            let mut s = String::new();
+            for arg in &args {
+                s += expect_str(code, arg)?;
+            }
+
+            Ok(Value::String(s))
+        }
+        CustomInstructionCode::StrLength => {
+            let s = args.first().map(|s| expect_str(code, s)).transpose()?;
+            let length = s.map(|s| s.chars().count()).unwrap_or(0);
+
+            Ok(Value::Int(Int::Int64(length as i64)))
+        }
+        CustomInstructionCode::Print => {
+            let mut written = 0;
+            for arg in &args {
+                let msg = expect_str(code, arg)?;
+                io.write_all(msg.as_bytes())?;
+                written += msg.len();
+            }
+            io.flush()?;
+
+            Ok(Value::Int(Int::Uint64(written as u64)))
+        }
+        CustomInstructionCode::Identity => Ok(args.into_iter().next().unwrap_or(Value::Void)),
+        CustomInstructionCode::Readline => {
+            // the optional argument is the prompt
+            if let Some(prompt) = args.first() {
+                io.write_all(expect_str(code, prompt)?.as_bytes())?;
+                io.flush()?;
+            }
+
+            Ok(Value::String(io.read_line()?))
+        }
+        CustomInstructionCode::IntAdd => {
+            let (a, b) = expect_ints(code, &args)?;
+            Ok(Value::Int(a.map2(b, |a, b| a + b)))
+        }
+        CustomInstructionCode::IntSub => {
+            let (a, b) = expect_ints(code, &args)?;
+            Ok(Value::Int(a.map2(b, |a, b| a - b)))
+        }
+        CustomInstructionCode::IntMul => {
+            let (a, b) = expect_ints(code, &args)?;
+            Ok(Value::Int(a.map2(b, |a, b| a.wrapping_mul(b))))
+        }
+        CustomInstructionCode::IntLt => {
+            let (a, b) = expect_ints(code, &args)?;
+            Ok(Value::Bool(a.as_i128() < b.as_i128()))
+        }
+        CustomInstructionCode::IntEq => {
+            let (a, b) = expect_ints(code, &args)?;
+            Ok(Value::Bool(a.as_i128() == b.as_i128()))
+        }
+    }
+}
+
+fn expect_ints(code: &CustomInstructionCode, args: &[Value]) -> Result<(Int, Int), RuntimeError> {
+    match args {
+        [Value::Int(a), Value::Int(b)] => Ok((*a, *b)),
+        _ => Err(RuntimeError::UnexpectedValue {
+            instruction: format!("{code:?}"),
+            got: args.first().map(Value::type_as_str).unwrap_or("Void"),
+        }),
+    }
+}
+
+fn expect_str<'v>(code: &CustomInstructionCode, value: &'v Value) -> Result<&'v str, RuntimeError> {
+    match value {
+        Value::String(s) => Ok(s),
+        value => Err(RuntimeError::UnexpectedValue {
+            instruction: format!("{code:?}"),
+            got: value.type_as_str(),
+        }),
+    }
+}
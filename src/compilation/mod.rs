@@ -4,3 +4,4 @@ mod interpreter;
 
 pub use context::*;
 pub use error::*;
+pub(crate) use interpreter::{run_custom, InterpreterContext};
@@ -0,0 +1,149 @@
+//! Rendering errors along with the source code they refer to.
+//!
+//! e.g.
+//! ```text
+//! error: greet not found
+//!   --> ./main.sol:7:9
+//!    |
+//!  7 |         greet name
+//!    |         ^^^^^
+//! ```
+
+use std::fmt::{self, Display};
+
+use crate::source_map::Span;
+
+/// Position of an error inside a source file,
+/// along with the line of source code for displaying it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: String,
+    /// starting at 1
+    pub line: usize,
+    /// starting at 1, counted in characters
+    pub column: usize,
+    source_line: String,
+    /// Number of characters to underline
+    length: usize,
+}
+
+impl Location {
+    /// Locates a span of source code, e.g. an identifier, see [`Span::within`].
+    /// The content is the one of the file, the span refers to.
+    /// Returns None, if the span lies outside of it.
+    pub fn from_span(file: &str, content: &str, span: Span) -> Option<Self> {
+        let offset = span.offset as usize;
+        let span = content.get(offset..offset + span.len as usize)?;
+
+        let before = &content[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let column = before[line_start..].chars().count() + 1;
+
+        let source_line = content[line_start..]
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+
+        // spans reaching over multiple lines are underlined until the end of the first line.
+        let length = span.lines().next().unwrap_or_default().chars().count().max(1);
+
+        Some(Location {
+            file: file.to_string(),
+            line,
+            column,
+            source_line,
+            length,
+        })
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let number = self.line.to_string();
+        let padding = " ".repeat(number.len());
+
+        // keep tabs, so that the caret lines up with the source line
+        let indent: String = self
+            .source_line
+            .chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        writeln!(f, "{padding}--> {}:{}:{}", self.file, self.line, self.column)?;
+        writeln!(f, "{padding} |")?;
+        writeln!(f, "{number} | {}", self.source_line)?;
+        write!(f, "{padding} | {indent}{}", "^".repeat(self.length))
+    }
+}
+
+/// Multiple errors, that got collected instead of stopping at the first one.
+#[derive(Debug)]
+pub struct Errors<E>(pub Vec<E>);
+
+impl<E: Display> Display for Errors<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for e in &self.0 {
+            writeln!(f, "error: {e:#}\n")?;
+        }
+
+        match self.0.len() {
+            1 => write!(f, "1 error"),
+            n => write!(f, "{n} errors"),
+        }
+    }
+}
+
+impl<E: Display + fmt::Debug> std::error::Error for Errors<E> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locates_span_by_line_and_column() {
+        let content = "fun main() =\n    greet name\n";
+        let span = &content[17..22];
+        assert_eq!(span, "greet");
+
+        let span = Span::within(0, content, span).unwrap();
+        let location = Location::from_span("main.sol", content, span).unwrap();
+        assert_eq!((location.line, location.column), (2, 5));
+        assert_eq!(
+            location.to_string(),
+            " --> main.sol:2:5\n  |\n2 |     greet name\n  |     ^^^^^"
+        );
+    }
+
+    #[test]
+    fn counts_columns_in_characters() {
+        let content = "let s = \"äöü\" ++ x";
+        let span = Span::within(0, content, &content[content.len() - 1..]).unwrap();
+
+        let location = Location::from_span("main.sol", content, span).unwrap();
+        assert_eq!((location.line, location.column), (1, 18));
+    }
+
+    #[test]
+    fn underlines_multiline_spans_until_the_end_of_the_line() {
+        let content = "fun main() =\n    1";
+        let span = Span::within(0, content, content).unwrap();
+        let location = Location::from_span("main.sol", content, span).unwrap();
+
+        assert_eq!(location.length, "fun main() =".len());
+    }
+
+    #[test]
+    fn rejects_spans_outside_of_the_content() {
+        let content = "fun main() = 1";
+        let span = Span {
+            file: 0,
+            offset: 10,
+            len: 10,
+        };
+
+        assert_eq!(Location::from_span("main.sol", content, span), None);
+    }
+}
@@ -1,8 +1,11 @@
+use serde::{Deserialize, Serialize};
+
 use crate::util::IdPath;
 
 
 /// Denotes either a symbol in local scope
 /// or in a module
+#[derive(Debug, Clone)]
 pub enum Symbol {
     LocalVar {addr: u16, ty: TypeId},
     Global(SymbolId)
@@ -20,7 +23,7 @@ pub type SSID = (SymbolId, Vec<TypeId>);
 pub type IdModule = IdPath;
 pub type IdFile = u16;
 
-#[derive(Debug, Hash, Copy, Clone, PartialEq, PartialOrd, Eq, Ord)]
+#[derive(Debug, Hash, Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Serialize, Deserialize)]
 pub enum IdItem {
     // Variable
     GlobalVar(u16),
@@ -31,6 +34,10 @@ pub enum IdItem {
     /// Points to a Type declared in the global scope
     Type(u16),
 
+    /// Points to a Test declared in the global scope.
+    /// Tests can't be referenced by other code, they are run by the test runner only.
+    Test(u16),
+
     /// Describes a auto-derived Method in terms of referencing the type and specific field OR Enum and specific Variant
     /// Note, enum variants MAY be constant
     /// Structure:
@@ -40,3 +47,26 @@ pub enum IdItem {
 
 pub type TypeId = usize;
 pub type FunctionId = usize;
+/// Slot of a global variable
+pub type GlobalId = usize;
+
+/// Declaration along with its type arguments, which are described the same way.
+/// Unlike the [`SSID`], it doesn't contain any [`TypeId`], which depend on the order of compilation.
+/// So it refers to the same function or type across compilations, e.g. inside the cache.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct StableId {
+    pub symbol: SymbolId,
+    pub args: Vec<StableId>,
+}
+
+impl StableId {
+    /// All modules, the declaration and its type arguments come from.
+    pub fn modules(&self) -> Vec<&IdModule> {
+        let mut modules = vec![&self.symbol.0];
+        for arg in &self.args {
+            modules.extend(arg.modules());
+        }
+
+        modules
+    }
+}
@@ -0,0 +1,14 @@
+//! Compiler and interpreters of the solar language.
+//! The `solar` binary is a command line interface around them, see [`cli`].
+pub mod bytecode;
+pub mod cli;
+pub mod compilation;
+mod diagnostics;
+pub mod id;
+pub mod mir;
+pub mod project;
+mod repl;
+pub mod source_map;
+mod types;
+mod util;
+pub mod value;
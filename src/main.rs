@@ -1,28 +1,11 @@
 // #![feature(string_leak)]
-pub mod mir;
-mod compilation;
-pub mod id;
-mod project;
-mod types;
-mod util;
-mod value;
+use std::process::ExitCode;
 
-use project::{read_all_projects, read_modules};
+use clap::Parser;
+use solar_interpreter::cli;
 
-use compilation::CompilerContext;
-
-fn main() {
-    let fsroot = std::env::args().nth(1).unwrap_or(".".to_string());
-    let project_info = read_all_projects(&fsroot).expect("read in solar project and dependencies");
-    let modules = read_modules(&project_info).expect("open and parse solar files");
-
-    let ctx = CompilerContext::with_default_io(&project_info, modules);
-
-    let f_main = ctx.find_target_main().expect("find main function");
-
-    // TODO instead call resolve_symbol(f_main, &[]) -> FunctionID
-    // -> why?
-    let function_id = ctx.compile_symbol(f_main, &[]).expect("compile code");
+fn main() -> ExitCode {
+    let cli = cli::Cli::parse();
 
     /* TODO
         There's a need now, to resolve types.
@@ -36,13 +19,11 @@ fn main() {
         E.g. Function(..Args) -> ByteCode
     */
 
-    eprintln!("\n{function_id:#?}");
-
-    // eprintln!("\n{:#?}", ctx.types);
-
-    for (k,_i, v) in ctx.functions.read().unwrap().iter(){
-        let k = k.0.0.join(".") + &format!(".{}", k.0.1);
-        eprintln!("{}:\n{:#?}\n", k, v);
-    } 
-
+    match cli::execute(cli.command) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {e:#}");
+            ExitCode::FAILURE
+        }
+    }
 }
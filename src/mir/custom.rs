@@ -1,8 +1,23 @@
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum CustomInstructionCode {
     StrConcat,
+    StrLength,
     Print,
     Identity,
     Readline,
+    IntAdd,
+    IntSub,
+    IntMul,
+    IntLt,
+    IntEq,
+}
+
+impl CustomInstructionCode {
+    /// Whether the instruction reads from stdin or writes to stdout.
+    pub fn performs_io(&self) -> bool {
+        matches!(self, Self::Print | Self::Readline)
+    }
 }
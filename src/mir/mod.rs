@@ -1,5 +1,5 @@
 mod custom;
-use crate::id::{FunctionId, TypeId};
+use crate::id::{FunctionId, GlobalId, TypeId};
 
 pub use custom::CustomInstructionCode;
 /// Expression with type-information
@@ -28,6 +28,9 @@ pub enum Instruction {
     },
     /// Get local Variable at address
     GetLocalVar(usize),
+    /// Get the value of a global variable.
+    /// Globals are evaluated once, during compilation.
+    GetGlobalVar(GlobalId),
     /// Define a new let binding, that can be referenced later
     NewLocalVar {
         // name: String,
@@ -44,11 +47,39 @@ pub enum Instruction {
         condition: StaticExpression,
         case_true: StaticExpression,
         case_false: StaticExpression,
+    },
+    /// Create an instance of a struct,
+    /// with the fields in order of their declaration.
+    NewStruct { fields: Vec<StaticExpression> },
+    /// Read the field of a struct instance
+    GetField {
+        value: StaticExpression,
+        field: u16,
+    },
+    /// Create a value of an enum
+    NewVariant {
+        variant: u16,
+        payload: Option<StaticExpression>,
+    },
+    /// Branch on the variant of an enum value
+    MatchVariant {
+        value: StaticExpression,
+        cases: Vec<VariantCase>,
+        /// Taken, if none of the cases match
+        default: Option<StaticExpression>,
     }, // we have differnt kinds of for loops and ifs.
        // Expressions and Statements
        // (loops) Expressions return an Array of static types, that must be the same
 }
 
+#[derive(Debug)]
+pub struct VariantCase {
+    pub variant: u16,
+    /// Local variable, the payload of the variant gets assigned to
+    pub binding: Option<u16>,
+    pub body: StaticExpression,
+}
+
 impl Instruction {
     pub fn expr(self, ty: TypeId) -> StaticExpression {
         StaticExpression { instr: Box::new(self), ty }
@@ -0,0 +1,350 @@
+//! Downloading dependencies from their git repositories into the solar path.
+//!
+//! e.g. the dependency
+//!     mylib(someone): https://example.com/someone/mylib.git@^0.1
+//! gets cloned at the highest matching tag, e.g. `0.1.2` (or `v0.1.2`), into
+//!     ~/.solar/libraries/mylib(someone)/0.1.2/
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use thiserror::Error;
+
+use super::{
+    get_solar_path, read_all_projects, ConfigError, Dependency, Lockfile, SolarConfig, VersionError,
+};
+
+#[derive(Debug, Error)]
+pub enum FetchError {
+    /// The dependency isn't installed and can't be downloaded
+    NoRepository { dependency: String },
+    /// Running git failed, e.g. because the tag doesn't exist
+    Git {
+        repo: String,
+        version: String,
+        message: String,
+    },
+    /// The solar.yaml of the fetched library doesn't match the dependency
+    Mismatch {
+        dependency: String,
+        field: &'static str,
+        expected: String,
+        found: String,
+    },
+    Version(#[from] VersionError),
+    Config(#[from] ConfigError),
+    Io(#[from] std::io::Error),
+}
+
+impl std::fmt::Display for FetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoRepository { dependency } => write!(
+                f,
+                "dependency {dependency} is not installed and has no repository to fetch it from"
+            ),
+            Self::Git {
+                repo,
+                version,
+                message,
+            } => write!(f, "fetching version {version} of {repo} failed: {message}"),
+            Self::Mismatch {
+                dependency,
+                field,
+                expected,
+                found,
+            } => write!(
+                f,
+                "fetched library does not match dependency {dependency}: expected {field} '{expected}', found '{found}'"
+            ),
+            Self::Version(e) => e.fmt(f),
+            Self::Config(e) => write!(f, "fetched library: {e}"),
+            Self::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+/// Fetches all dependencies of the project, that aren't installed yet,
+/// along with their own dependencies.
+/// Versions locked in the solar.lock are fetched, as long as they satisfy the requirements.
+/// Afterwards the solar.lock is updated, if the set of dependencies changed.
+/// Returns the fetched dependencies, along with the version that got fetched.
+pub fn fetch_all(fsroot: &str) -> anyhow::Result<Vec<(Dependency, String)>> {
+    let config = SolarConfig::read(&format!("{fsroot}/solar.yaml"))?;
+    let locked = Lockfile::find(fsroot)?;
+    let solar_path = get_solar_path();
+
+    // dependencies, along with the root of the project declaring them
+    let mut pending = config
+        .deps()
+        .into_iter()
+        .map(|dep| (dep, fsroot.to_string()))
+        .collect::<Vec<_>>();
+    let mut seen = HashSet::new();
+    let mut fetched = Vec::new();
+
+    while let Some((dep, root)) = pending.pop() {
+        // local dependencies can't be fetched, but their dependencies might need to be.
+        if let Some(dir) = dep.local_dir(&root)? {
+            if seen.insert(vec![dir.clone()]) {
+                let config = SolarConfig::read(&format!("{dir}/solar.yaml"))?;
+                pending.extend(config.deps().into_iter().map(|dep| (dep, dir.clone())));
+            }
+            continue;
+        }
+
+        let locked_version = locked.as_ref().and_then(|locked| locked.locked_version(&dep));
+        let (version, missing) = match locked_version {
+            Some(version) => {
+                let missing = !Path::new(&dep.dir_in(&solar_path, &version)).is_dir();
+                (version, missing)
+            }
+            None => match dep.resolve() {
+                Ok(version) => (version, false),
+                Err(VersionError::NoMatch { .. }) => (remote_version(&dep)?, true),
+                Err(e) => return Err(e.into()),
+            },
+        };
+
+        if !seen.insert(dep.basepath(&version)) {
+            continue;
+        }
+
+        if missing {
+            fetch(&dep, &version, &solar_path)?;
+        }
+
+        let dir = dep.dir_in(&solar_path, &version);
+        let config = SolarConfig::read(&format!("{dir}/solar.yaml"))?;
+        pending.extend(config.deps().into_iter().map(|dep| (dep, dir.clone())));
+
+        if missing {
+            fetched.push((dep, version));
+        }
+    }
+
+    // reading the projects writes the lockfile, once the dependencies are installed.
+    read_all_projects(fsroot)?;
+
+    Ok(fetched)
+}
+
+/// Clones the repository of the dependency at the tag of the version
+/// into the libraries directory of the given solar path.
+/// The library is checked out into a temporary directory first,
+/// so that failed fetches leave nothing behind.
+pub fn fetch(dep: &Dependency, version: &str, solar_path: &str) -> Result<(), FetchError> {
+    let Some(repo) = &dep.repo else {
+        return Err(FetchError::NoRepository {
+            dependency: dep.key(),
+        });
+    };
+
+    let target = PathBuf::from(dep.dir_in(solar_path, version));
+    let parent = target.parent().expect("library directory to have a parent");
+    fs::create_dir_all(parent)?;
+
+    let tmp = parent.join(format!(".{version}.fetching"));
+    if tmp.exists() {
+        fs::remove_dir_all(&tmp)?;
+    }
+
+    let result = clone(repo, version, &tmp).and_then(|()| verify(dep, version, &tmp));
+    if let Err(e) = result {
+        // nothing to clean up, if cloning failed before creating the directory.
+        let _ = fs::remove_dir_all(&tmp);
+        return Err(e);
+    }
+
+    // libraries are plain source trees.
+    fs::remove_dir_all(tmp.join(".git"))?;
+    fs::rename(&tmp, &target)?;
+
+    Ok(())
+}
+
+/// Highest version tagged in the repository of the dependency, that satisfies its requirement.
+fn remote_version(dep: &Dependency) -> Result<String, FetchError> {
+    let Some(repo) = &dep.repo else {
+        return Err(FetchError::NoRepository {
+            dependency: dep.key(),
+        });
+    };
+
+    let output = Command::new("git")
+        .args(["ls-remote", "--tags", "--refs", "--", repo])
+        .output()?;
+
+    if !output.status.success() {
+        return Err(FetchError::Git {
+            repo: repo.to_string(),
+            version: dep.requirement.clone(),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+
+    // e.g. 3f786850e387550fdab836ed7e6dc881de23001b	refs/tags/v0.1.0
+    let tags = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once("refs/tags/"))
+        .map(|(_, tag)| tag.trim_start_matches('v').to_string())
+        .collect::<Vec<_>>();
+
+    Ok(dep.resolve_among(&tags)?)
+}
+
+/// Clones a single tag of the repository.
+/// Tags may be named after the version, with or without a leading `v`.
+/// The repository comes from a solar.yaml, so it's separated by `--`,
+/// to never be mistaken for an option of git.
+fn clone(repo: &str, version: &str, dir: &Path) -> Result<(), FetchError> {
+    let mut message = String::new();
+
+    for tag in [version.to_string(), format!("v{version}")] {
+        let output = Command::new("git")
+            .args([
+                "clone", "--quiet", "--depth", "1", "--branch", &tag, "--", repo,
+            ])
+            .arg(dir)
+            .output()?;
+
+        if output.status.success() {
+            return Ok(());
+        }
+
+        message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    }
+
+    Err(FetchError::Git {
+        repo: repo.to_string(),
+        version: version.to_string(),
+        message,
+    })
+}
+
+/// Checks, that the fetched library is the one we asked for.
+fn verify(dep: &Dependency, version: &str, dir: &Path) -> Result<(), FetchError> {
+    let solarfile = dir.join("solar.yaml");
+    let config = SolarConfig::read(solarfile.to_str().expect("path to be utf-8"))?;
+
+    let publisher = config.publisher.clone().unwrap_or_default();
+    let version = version.to_string();
+    let fields = [
+        ("name", &dep.name, &config.name),
+        ("publisher", &dep.publisher, &publisher),
+        ("version", &version, &config.version),
+    ];
+
+    for (field, expected, found) in fields {
+        if expected != found {
+            return Err(FetchError::Mismatch {
+                dependency: dep.key(),
+                field,
+                expected: expected.clone(),
+                found: found.clone(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::DependencySpec;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let output = Command::new("git")
+            .args([
+                "-c",
+                "user.name=solar",
+                "-c",
+                "user.email=solar@example.com",
+            ])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .expect("run git");
+
+        assert!(
+            output.status.success(),
+            "git {args:?}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    /// Bare repository of a library, tagged with its version.
+    /// Returns the path of the repository.
+    fn bare_repo(root: &Path, solar_yaml: &str, tag: &str) -> String {
+        let work = root.join("work");
+        fs::create_dir_all(&work).unwrap();
+        fs::write(work.join("solar.yaml"), solar_yaml).unwrap();
+        fs::write(work.join("lib.sol"), "fun answer() = 42\n").unwrap();
+
+        git(&work, &["init", "--quiet"]);
+        git(&work, &["add", "."]);
+        git(&work, &["commit", "--quiet", "-m", "release"]);
+        git(&work, &["tag", tag]);
+
+        let bare = root.join("mylib.git");
+        git(root, &["init", "--quiet", "--bare", "mylib.git"]);
+        git(&work, &["push", "--quiet", bare.to_str().unwrap(), tag]);
+
+        bare.to_str().unwrap().to_string()
+    }
+
+    fn dependency(value: &str) -> Dependency {
+        let value = DependencySpec::Version(value.to_string());
+        Dependency::from_key_value("mylib(someone)", &value).unwrap()
+    }
+
+    #[test]
+    fn fetches_tagged_version_from_local_bare_repo() {
+        let root = std::env::temp_dir().join(format!("solar-fetch-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+
+        let yaml = "name: mylib\npublisher: someone\nversion: 0.1.0\n";
+        let repo = bare_repo(&root, yaml, "v0.1.0");
+
+        let solar_path = format!("{}/solar/", root.display());
+
+        let dep = dependency(&format!("{repo}@^0.1"));
+        let version = remote_version(&dep).unwrap();
+        assert_eq!(version, "0.1.0");
+
+        fetch(&dep, &version, &solar_path).unwrap();
+
+        let dir = PathBuf::from(dep.dir_in(&solar_path, &version));
+        assert!(dir.join("solar.yaml").is_file());
+        assert!(dir.join("lib.sol").is_file());
+        assert!(!dir.join(".git").exists());
+
+        // a library, that isn't the one asked for, leaves nothing behind
+        let other = dependency(&format!("{repo}@^0.1"));
+        let other = Dependency {
+            name: "otherlib".to_string(),
+            ..other
+        };
+        let result = fetch(&other, &version, &solar_path);
+        assert!(matches!(
+            result,
+            Err(FetchError::Mismatch { field: "name", .. })
+        ));
+        assert!(!Path::new(&other.dir_in(&solar_path, &version)).exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn rejects_repositories_looking_like_options() {
+        let value = DependencySpec::Version("--upload-pack=touch /tmp/pwned@^0.1".to_string());
+
+        assert!(Dependency::from_key_value("mylib(someone)", &value).is_err());
+    }
+}
@@ -1,11 +1,12 @@
-use super::Module;
 /// This file contains code
 /// for reading in dependencies and libraries.
 /// and resolving their imports.
-use crate::project::{FileInfo, SolarConfig};
+use crate::project::{FileInfo, ResolveError, SolarConfig};
+use crate::source_map::{FileId, SourceFile};
 use crate::util::IdPath;
 use anyhow::Context;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
 /// Contains information on a project,
@@ -18,7 +19,8 @@ pub struct Project {
     pub fsroot: String,
 
     /// mapping needed to resolve imports in a project to actual
-    /// dependencies
+    /// dependencies.
+    /// Filled in, once the versions of the dependencies are resolved.
     pub dep_map: HashMap<String, IdPath>,
 
     /// Solarconfig of this project
@@ -39,36 +41,88 @@ impl Project {
 
         // solar config file of the project
         let config = SolarConfig::read(&solarfile)?;
+
+        Ok(Self::new(fsroot, basepath, config))
+    }
+
+    /// Wraps a single solar file into an implicit project,
+    /// so that it can be run without a solar.yaml.
+    /// The fsroot of the project is the file itself.
+    pub fn script(file: &str, basepath: IdPath) -> Project {
+        let name = Path::new(file)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("script");
+
+        let config = SolarConfig::script(name);
+
+        Self::new(file, basepath, config)
+    }
+
+    /// Implicit project without any files on disk,
+    /// e.g. the one of the REPL, which adds its files later on.
+    pub fn empty(name: &str, basepath: IdPath) -> Project {
+        let config = SolarConfig::script(name);
+
+        Self::new("", basepath, config)
+    }
+
+    pub(super) fn new(fsroot: &str, basepath: IdPath, config: SolarConfig) -> Project {
         let fsroot = fsroot.to_string();
 
-        let dep_map = config
-            .deps()
-            .into_iter()
-            .map(|d| {
-                let value = d.basepath();
-                let key = d.name;
-                (key, value)
-            })
-            .collect();
-
-        Ok(Project {
+        Project {
             basepath,
             fsroot,
-            dep_map,
+            dep_map: HashMap::new(),
             config,
-        })
+        }
     }
 
-    /// Reads and parses
-    /// all solarfiles, organized into modules,
-    /// into memory.
-    /// the project id is supposed to be a reference to this exact [Project].
-    ///
-    /// Returns a Mapping of  {ModulePath => Module}
-    pub fn read_all(&self, project_id: usize) -> anyhow::Result<HashMap<IdPath, Module>> {
-        let mut map = HashMap::new();
+    /// Whether this project is a local dependency, see [`SolarConfig::local_basepath`].
+    pub fn is_local(&self) -> bool {
+        self.basepath
+            .get(1)
+            .is_some_and(|version| version.starts_with("path:"))
+    }
 
-        for entry in WalkDir::new(&self.fsroot) {
+    /// Whether this project consists of a single file only.
+    pub fn is_script(&self) -> bool {
+        Path::new(&self.fsroot).is_file()
+    }
+
+    /// Directory, that the paths of the files are relative to.
+    /// A script lives in the root module of its project.
+    pub fn root(&self) -> &Path {
+        if self.is_script() {
+            Path::new(&self.fsroot).parent().unwrap_or(Path::new(""))
+        } else {
+            Path::new(&self.fsroot)
+        }
+    }
+
+    /// All solar files of the project, that aren't excluded.
+    pub fn source_files(&self) -> Vec<PathBuf> {
+        if self.fsroot.is_empty() {
+            return Vec::new();
+        }
+
+        let root = self.root();
+
+        // validated, when the config was read.
+        let exclude = self
+            .config
+            .exclude_patterns()
+            .expect("exclude patterns to be valid");
+
+        // sorted, so that the files of a module are always in the same order
+        let walk = WalkDir::new(&self.fsroot).sort_by_file_name();
+        let entries = walk.into_iter().filter_entry(|entry| {
+            let path = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            !exclude.iter().any(|pattern| pattern.matches_path(path))
+        });
+
+        let mut files = Vec::new();
+        for entry in entries {
             let Ok(entry) = entry else {
                 eprintln!("error walking directory: {entry:?}");
                 continue;
@@ -83,55 +137,53 @@ impl Project {
                 continue;
             }
 
-            let path = entry.path();
-            // We need to strip the path,
-            // because we don't care about the root file system
-            let filepath = path
-                .strip_prefix(&self.fsroot)
-                .expect("to strip common prefix of filepath");
-
-            // absolute id path.
-            let mut idmodule = self
-                .basepath
-                .iter()
-                .cloned()
-                .chain(
-                    filepath
-                        .iter()
-                        .map(|f| f.to_str().expect("receive str from OsString").to_string()),
-                )
-                .collect::<Vec<_>>();
-            // remove filename from IDmodule.
-            idmodule.pop().unwrap();
-
-            // read in source code of file.
-            // and leak the memory.
-            // NOTE: for now we just keep all the sourcefiles in memory.
-            // maybe later we will do the opposite and never keep them in memory, but instead read them when needed only.
-            // This requires changes to the AST Nodes. All have a span: &str.
-            // We'd prefer to have a token_start: u32 on over node. (We don't REALLY need token_end)
-            // and remember for each Ast the file name.
-            let mut source_code = std::fs::read_to_string(path).expect("read solar file");
-            source_code.shrink_to_fit();
-            let content = leak_string(source_code);
-
-            let path = path.to_str().expect("read filename").to_string();
-            let context = format!("reading file {path}");
-            let fileinfo = FileInfo::from_code(path, &self.dep_map, &self.basepath, content)
-                .context(context)?;
-
-            map.entry(idmodule)
-                .or_insert(Module::new(project_id))
-                .add_file(fileinfo);
+            files.push(entry.into_path());
         }
 
-        Ok(map)
+        files
     }
-}
 
-fn leak_string(s: String) -> &'static str {
-    let b = s.into_boxed_str();
-    let b = Box::leak(b);
+    /// Path of the module, a file of the project belongs to.
+    pub fn module_path(&self, path: &Path) -> IdPath {
+        // We need to strip the path,
+        // because we don't care about the root file system
+        let filepath = path
+            .strip_prefix(self.root())
+            .expect("to strip common prefix of filepath");
+
+        // absolute id path.
+        let mut idmodule = self
+            .basepath
+            .iter()
+            .cloned()
+            .chain(
+                filepath
+                    .iter()
+                    .map(|f| f.to_str().expect("receive str from OsString").to_string()),
+            )
+            .collect::<Vec<_>>();
+        // remove filename from IDmodule.
+        idmodule.pop().unwrap();
+
+        idmodule
+    }
 
-    unsafe { std::mem::transmute(b) }
+    /// Parses a single solar file of the project, that has been loaded into the source map.
+    /// Returns the path of the module, the file belongs to.
+    pub fn parse_file<'a>(
+        &self,
+        id: FileId,
+        source: &'a SourceFile,
+    ) -> anyhow::Result<(IdPath, FileInfo<'a>)> {
+        let idmodule = self.module_path(Path::new(&source.path));
+
+        let path = source.path.clone();
+        let context = format!("reading file {path}");
+        let fileinfo =
+            FileInfo::from_code(path, id, &self.dep_map, &self.basepath, &source.content)
+                .map_err(ResolveError::into_owned)
+                .context(context)?;
+
+        Ok((idmodule, fileinfo))
+    }
 }
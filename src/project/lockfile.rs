@@ -0,0 +1,387 @@
+//! The solar.lock file records the resolved dependency graph of a project,
+//! along with a hash of the content of each library.
+//!
+//! Versions are resolved to the locked ones, as long as they still satisfy the requirements.
+//! The lockfile is (re-)written, when it doesn't exist yet or the set of dependencies changes,
+//! e.g. after `solar fetch` or editing the solar.yaml.
+//! On every other run, the locked libraries are checked against it,
+//! so that modified libraries inside the solar path are noticed.
+
+use std::{collections::HashMap, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use walkdir::WalkDir;
+
+use super::{Dependency, ProjectInfo};
+use crate::util::{self, IdPath};
+
+/// Structure of the solar.lock file
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub libraries: Vec<LockedLibrary>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedLibrary {
+    pub name: String,
+    pub publisher: String,
+    pub version: String,
+    pub repo: Option<String>,
+    /// sha256 of all files of the library
+    pub hash: String,
+}
+
+#[derive(Debug, Error)]
+pub enum LockError {
+    /// The content of a library differs from the one recorded in the lockfile
+    Modified {
+        library: String,
+        version: String,
+        dir: String,
+        expected: String,
+        found: String,
+    },
+    /// The locked version of a library isn't installed in the solar path
+    NotInstalled {
+        library: String,
+        version: String,
+    },
+    Yaml(#[from] serde_yaml::Error),
+    Io(#[from] std::io::Error),
+}
+
+impl std::fmt::Display for LockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Modified {
+                library,
+                version,
+                dir,
+                expected,
+                found,
+            } => write!(
+                f,
+                "library {library} {version} in {dir} has been modified since solar.lock was written. \
+                expected hash {expected}, found {found}. \
+                Delete solar.lock, if the modification is intended"
+            ),
+            Self::NotInstalled { library, version } => write!(
+                f,
+                "library {library} {version} is locked in solar.lock, but not installed. Run `solar fetch` to download it"
+            ),
+            Self::Yaml(e) => write!(f, "reading solar.lock: {e}"),
+            Self::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Lockfile {
+    /// Records all dependencies of the target project.
+    pub fn from_projects(projects: &ProjectInfo) -> Result<Self, LockError> {
+        let libraries = libraries(projects)
+            .into_iter()
+            .map(|(library, dir)| {
+                let hash = hash_dir(dir)?;
+                Ok(LockedLibrary { hash, ..library })
+            })
+            .collect::<Result<_, LockError>>()?;
+
+        Ok(Lockfile { libraries })
+    }
+
+    /// Reads the solar.lock of the project, if there is one.
+    pub fn find(fsroot: &str) -> Result<Option<Self>, LockError> {
+        let path = format!("{fsroot}/solar.lock");
+        if !Path::new(&path).exists() {
+            return Ok(None);
+        }
+
+        Self::read(&path).map(Some)
+    }
+
+    pub fn read(path: &str) -> Result<Self, LockError> {
+        let content = fs::read_to_string(path)?;
+        let lockfile = serde_yaml::from_str(&content)?;
+
+        Ok(lockfile)
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), LockError> {
+        let content = serde_yaml::to_string(self)?;
+        fs::write(path, content)?;
+
+        Ok(())
+    }
+
+    /// Highest locked version of the library, that satisfies the requirement of the dependency.
+    /// Multiple versions may be locked, if dependents require versions, that don't overlap.
+    pub fn locked_version(&self, dep: &Dependency) -> Option<String> {
+        let versions = self
+            .libraries
+            .iter()
+            .filter(|l| l.name == dep.name && l.publisher == dep.publisher)
+            .map(|l| l.version.clone())
+            .collect::<Vec<_>>();
+
+        dep.resolve_among(&versions).ok()
+    }
+
+    /// Checks, that all used libraries, that are locked, still have the same content.
+    /// Only locked libraries get hashed, new ones are recorded once the lockfile is written.
+    fn verify(&self, projects: &ProjectInfo) -> Result<(), LockError> {
+        for (library, dir) in libraries(projects) {
+            let Some(locked) = self.libraries.iter().find(|l| l.same_library(&library)) else {
+                continue;
+            };
+
+            let found = hash_dir(dir)?;
+            if locked.hash != found {
+                return Err(LockError::Modified {
+                    library: format!("{}({})", library.name, library.publisher),
+                    version: library.version,
+                    dir: dir.to_string(),
+                    expected: locked.hash.clone(),
+                    found,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the projects use exactly the locked libraries, ignoring their content.
+    fn same_dependencies(&self, projects: &ProjectInfo) -> bool {
+        let used = libraries(projects);
+
+        used.len() == self.libraries.len()
+            && used
+                .iter()
+                .zip(&self.libraries)
+                .all(|((used, _), locked)| used.same_library(locked) && used.repo == locked.repo)
+    }
+}
+
+impl LockedLibrary {
+    fn same_library(&self, other: &LockedLibrary) -> bool {
+        self.name == other.name
+            && self.publisher == other.publisher
+            && self.version == other.version
+    }
+}
+
+/// The libraries the target project depends on, along with their directory, sorted.
+/// Their hashes are left empty, as hashing is only needed for some of them.
+fn libraries(projects: &ProjectInfo) -> Vec<(LockedLibrary, &str)> {
+    // Repositories are declared by the dependents.
+    let mut repos: HashMap<IdPath, String> = HashMap::new();
+    for (_, project) in projects.iter_values() {
+        for dep in project.config.deps() {
+            let (Some(repo), Some(basepath)) = (&dep.repo, project.dep_map.get(&dep.name)) else {
+                continue;
+            };
+
+            repos.insert(basepath.clone(), repo.clone());
+        }
+    }
+
+    let mut libraries = Vec::new();
+    for (basepath, _, project) in projects.iter() {
+        // Local dependencies change during development all the time.
+        if *basepath == util::target_id() || project.is_local() {
+            continue;
+        }
+
+        let library = LockedLibrary {
+            name: project.config.name.clone(),
+            publisher: project.config.publisher.clone().unwrap_or_default(),
+            version: basepath[1].clone(),
+            repo: repos.get(basepath).cloned(),
+            hash: String::new(),
+        };
+        libraries.push((library, project.fsroot.as_str()));
+    }
+
+    libraries.sort_by(|(a, _), (b, _)| {
+        (&a.name, &a.publisher, &a.version).cmp(&(&b.name, &b.publisher, &b.version))
+    });
+
+    libraries
+}
+
+/// Resolves the requirement of the dependency to a locked version, if there is one.
+/// The locked version has to be installed, instead of silently switching to another one.
+pub fn resolve_locked(dep: &Dependency, locked: &Lockfile) -> Result<Option<String>, LockError> {
+    let Some(version) = locked.locked_version(dep) else {
+        return Ok(None);
+    };
+
+    if !Path::new(&dep.dir(&version)).is_dir() {
+        return Err(LockError::NotInstalled {
+            library: dep.key(),
+            version,
+        });
+    }
+
+    Ok(Some(version))
+}
+
+/// Checks the dependencies of the project against its solar.lock, if there is one.
+/// If updating is allowed, the lockfile gets (re-)written,
+/// if it doesn't exist yet, or the set of dependencies has changed.
+pub fn check_lockfile(
+    fsroot: &str,
+    locked: Option<&Lockfile>,
+    projects: &ProjectInfo,
+    update: bool,
+) -> Result<(), LockError> {
+    if let Some(locked) = locked {
+        locked.verify(projects)?;
+
+        if locked.same_dependencies(projects) {
+            return Ok(());
+        }
+    }
+
+    if !update {
+        return Ok(());
+    }
+
+    Lockfile::from_projects(projects)?.write(&format!("{fsroot}/solar.lock"))
+}
+
+/// Hashes the paths and contents of all files inside the directory.
+/// Files are visited in a fixed order, so that the hash doesn't depend on the filesystem.
+fn hash_dir(dir: &str) -> Result<String, LockError> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry.map_err(std::io::Error::from)?;
+        if entry.file_type().is_file() {
+            files.push(entry.into_path());
+        }
+    }
+
+    let mut hasher = Sha256::new();
+    for file in files {
+        let relative = file.strip_prefix(dir).unwrap_or(&file);
+        let content = fs::read(&file)?;
+
+        // lengths are included, so that content can't be shifted between files.
+        let name = relative.to_string_lossy();
+        hasher.update((name.len() as u64).to_le_bytes());
+        hasher.update(name.as_bytes());
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(&content);
+    }
+
+    let hash = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+
+    Ok(format!("sha256:{hash}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use hotel::HotelMap;
+
+    use super::*;
+    use crate::project::{DependencySpec, Project, SolarConfig};
+
+    /// Project info containing version 0.1.0 of mylib, which consists of one file.
+    /// Its directory is removed, once the fixture is dropped.
+    struct Library {
+        dir: PathBuf,
+        projects: ProjectInfo,
+    }
+
+    impl Drop for Library {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn library(test: &str, content: &str) -> Library {
+        let dir = std::env::temp_dir().join(format!("solar-lock-{}-{test}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("lib.sol"), content).unwrap();
+
+        let config = "name: mylib\npublisher: someone\nversion: 0.1.0\n";
+        let config: SolarConfig = serde_yaml::from_str(config).unwrap();
+        let basepath = vec!["mylib(someone)".to_string(), "0.1.0".to_string()];
+
+        let mut projects = HotelMap::new();
+        let project = Project::new(dir.to_str().unwrap(), basepath.clone(), config);
+        projects.insert(basepath, project);
+
+        Library { dir, projects }
+    }
+
+    fn locked(version: &str) -> LockedLibrary {
+        LockedLibrary {
+            name: "mylib".to_string(),
+            publisher: "someone".to_string(),
+            version: version.to_string(),
+            repo: None,
+            hash: String::new(),
+        }
+    }
+
+    #[test]
+    fn accepts_unmodified_libraries() {
+        let library = library("unmodified", "fun answer() = 42\n");
+        let lockfile = Lockfile::from_projects(&library.projects).unwrap();
+
+        assert!(lockfile.verify(&library.projects).is_ok());
+        assert!(lockfile.same_dependencies(&library.projects));
+    }
+
+    #[test]
+    fn detects_modified_libraries() {
+        let lockfile =
+            Lockfile::from_projects(&library("modified", "fun answer() = 42\n").projects).unwrap();
+        let library = library("modified", "fun answer() = 41\n");
+
+        let result = lockfile.verify(&library.projects);
+        assert!(matches!(result, Err(LockError::Modified { version, .. }) if version == "0.1.0"));
+    }
+
+    #[test]
+    fn new_libraries_change_the_dependencies() {
+        let library = library("new", "fun answer() = 42\n");
+        let lockfile = Lockfile {
+            libraries: Vec::new(),
+        };
+
+        // nothing to compare against, until the lockfile gets written
+        assert!(lockfile.verify(&library.projects).is_ok());
+        assert!(!lockfile.same_dependencies(&library.projects));
+    }
+
+    #[test]
+    fn prefers_locked_versions_satisfying_the_requirement() {
+        let lockfile = Lockfile {
+            libraries: vec![locked("0.1.1"), locked("0.2.0")],
+        };
+
+        let dep = |requirement: &str| {
+            let value = DependencySpec::Version(requirement.to_string());
+            Dependency::from_key_value("mylib(someone)", &value).unwrap()
+        };
+
+        assert_eq!(
+            lockfile.locked_version(&dep("^0.1")),
+            Some("0.1.1".to_string())
+        );
+        assert_eq!(
+            lockfile.locked_version(&dep(">=0.1")),
+            Some("0.2.0".to_string())
+        );
+        assert_eq!(lockfile.locked_version(&dep("^1")), None);
+    }
+}
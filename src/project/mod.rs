@@ -1,67 +1,268 @@
+mod fetch;
 mod libraries;
+mod lockfile;
 mod modules;
 mod project_info;
+mod version;
+pub use fetch::*;
 pub use libraries::*;
+pub use lockfile::*;
 pub use modules::*;
 pub use project_info::*;
+pub use version::*;
 
 use crate::util::IdPath;
+use anyhow::Context;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{collections::HashMap, path::Path};
+use thiserror::Error;
 
 /// Structure of the solar.yaml file
+/// Unknown keys are rejected, so that typos don't go unnoticed.
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct SolarConfig {
     pub name: String,
     pub publisher: Option<String>,
+    /// Semantic version, e.g. `0.1.0`
     pub version: String,
     pub description: Option<String>,
+    pub license: Option<String>,
+    /// Edition of the solar language, the project is written in
+    pub edition: Option<String>,
+
+    /// Function the project starts with, e.g. `main`.
+    /// Functions in submodules are written with their module path, e.g. `cli.main`.
+    pub entry: Option<String>,
 
     author: Option<String>,
     authors: Option<Vec<String>>,
 
-    dependencies: Option<HashMap<String, String>>,
+    dependencies: Option<HashMap<String, DependencySpec>>,
+    /// Dependencies, that are only used by tests
+    #[serde(rename = "dev-dependencies")]
+    dev_dependencies: Option<HashMap<String, DependencySpec>>,
+
+    /// Glob patterns of files, that don't belong to the project,
+    /// relative to the root of the project. e.g. `examples/**`
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// How to report libraries, that are used in multiple versions
+    #[serde(rename = "version-conflicts", default)]
+    pub version_conflicts: ConflictPolicy,
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    Read {
+        path: String,
+        error: std::io::Error,
+    },
+    /// Includes unknown keys and values of the wrong type
+    Yaml {
+        path: String,
+        error: serde_yaml::Error,
+    },
+    Version {
+        path: String,
+        version: String,
+        error: semver::Error,
+    },
+    DependencyKey {
+        path: String,
+        message: String,
+    },
+    Requirement {
+        path: String,
+        error: VersionError,
+    },
+    Exclude {
+        path: String,
+        pattern: String,
+        error: glob::PatternError,
+    },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read { path, error } => write!(f, "reading {path}: {error}"),
+            Self::Yaml { path, error } => write!(f, "invalid {path}: {error}"),
+            Self::Version {
+                path,
+                version,
+                error,
+            } => write!(
+                f,
+                "invalid {path}: version '{version}' is not a semantic version (e.g. 0.1.0): {error}"
+            ),
+            Self::DependencyKey { path, message } => write!(f, "invalid {path}: {message}"),
+            Self::Requirement { path, error } => write!(f, "invalid {path}: {error}"),
+            Self::Exclude {
+                path,
+                pattern,
+                error,
+            } => write!(f, "invalid {path}: exclude pattern '{pattern}': {error}"),
+        }
+    }
+}
+
+/// Value of a dependency inside the solar.yaml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum DependencySpec {
+    /// Version requirement, optionally preceded by a git repository.
+    /// e.g. `^0.1` or `https://example.com/someone/mylib.git@^0.1`
+    Version(String),
+    /// Project in the local filesystem, relative to the declaring project.
+    /// e.g. `{ path: ../mylib }`
+    Local { path: String },
+}
+
+/// How to report libraries, that are used in multiple versions.
+/// e.g. the project depends on `std ^0.1` and a library on `std ^0.2`.
+/// Both versions are loaded, unless it's an error.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    Allow,
+    #[default]
+    Warn,
+    Error,
 }
 
 impl SolarConfig {
-    pub fn read(path: &str) -> anyhow::Result<Self> {
-        let content = std::fs::read_to_string(path)?;
-        let cfg: Self = serde_yaml::from_str(&content)?;
+    /// Reads and validates a solar.yaml
+    pub fn read(path: &str) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path).map_err(|error| ConfigError::Read {
+            path: path.to_string(),
+            error,
+        })?;
+        let cfg: Self = serde_yaml::from_str(&content).map_err(|error| ConfigError::Yaml {
+            path: path.to_string(),
+            error,
+        })?;
+
+        cfg.validate(path)?;
 
         Ok(cfg)
     }
 
-    /*
-        pub fn basepath(&self) -> IdPath {
-            let publisher = if let Some(p) = &self.publisher { p } else { "" };
+    /// Checks everything, that can't be checked by deserializing alone.
+    fn validate(&self, path: &str) -> Result<(), ConfigError> {
+        if let Err(error) = semver::Version::parse(&self.version) {
+            return Err(ConfigError::Version {
+                path: path.to_string(),
+                version: self.version.clone(),
+                error,
+            });
+        }
+
+        let deps = self.dependencies.iter().chain(&self.dev_dependencies).flatten();
+        for (key, value) in deps {
+            let dep = Dependency::from_key_value(key, value).map_err(|message| {
+                ConfigError::DependencyKey {
+                    path: path.to_string(),
+                    message,
+                }
+            })?;
 
-            vec![
-                format!("{}({})", self.name, publisher),
-                self.version.to_owned(),
-            ]
+            if dep.path.is_none() {
+                dep.parse_requirement()
+                    .map_err(|error| ConfigError::Requirement {
+                        path: path.to_string(),
+                        error,
+                    })?;
+            }
         }
 
-        pub fn authors(&self) -> Vec<String> {
-            let mut v = Vec::new();
-            if let Some(a) = &self.author {
+        if let Err((pattern, error)) = self.exclude_patterns() {
+            return Err(ConfigError::Exclude {
+                path: path.to_string(),
+                pattern,
+                error,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Configuration of the implicit project around a single solar file,
+    /// that is run without a solar.yaml.
+    /// The newest installed std library is its only dependency.
+    pub fn script(name: &str) -> Self {
+        let dependencies = HashMap::from([(
+            "std(solar-lang)".to_string(),
+            DependencySpec::Version("*".to_string()),
+        )]);
+
+        SolarConfig {
+            name: name.to_string(),
+            publisher: None,
+            version: "0.0.0".to_string(),
+            description: None,
+            license: None,
+            edition: None,
+            entry: None,
+            author: None,
+            authors: None,
+            dependencies: Some(dependencies),
+            dev_dependencies: None,
+            exclude: Vec::new(),
+            version_conflicts: ConflictPolicy::default(),
+        }
+    }
+
+    pub fn authors(&self) -> Vec<String> {
+        let mut v = Vec::new();
+        if let Some(a) = &self.author {
+            v.push(a.to_string());
+        }
+
+        if let Some(authors) = &self.authors {
+            for a in authors {
                 v.push(a.to_string());
             }
+        }
 
-            if let Some(authors) = &self.authors {
-                for a in authors {
-                    v.push(a.to_string());
-                }
-            }
+        v
+    }
 
-            v
-        }
-    */
+    /// Compiled exclude patterns.
+    /// Returns the first invalid pattern otherwise.
+    pub fn exclude_patterns(&self) -> Result<Vec<glob::Pattern>, (String, glob::PatternError)> {
+        self.exclude
+            .iter()
+            .map(|pattern| glob::Pattern::new(pattern).map_err(|e| (pattern.clone(), e)))
+            .collect()
+    }
+
+    /// Turns the dev-dependencies into regular dependencies, e.g. for running tests.
+    pub fn include_dev_dependencies(&mut self) {
+        let Some(dev_dependencies) = self.dev_dependencies.take() else {
+            return;
+        };
+
+        self.dependencies
+            .get_or_insert_with(HashMap::new)
+            .extend(dev_dependencies);
+    }
+
+    /// Unique Id of a project used as local dependency.
+    /// The version is replaced by the directory, because it may change at any time.
+    pub fn local_basepath(&self, dir: &str) -> IdPath {
+        let publisher = self.publisher.as_deref().unwrap_or_default();
+
+        vec![format!("{}({publisher})", self.name), format!("path:{dir}")]
+    }
 
     pub fn deps(&self) -> Vec<Dependency> {
         let Some(deps) = &self.dependencies else {
             return Vec::new();
         };
 
+        // dependencies are validated, when the config is read.
         deps.iter()
             .map(|(key, value)| Dependency::from_key_value(key, value).expect("read dependencies"))
             .collect()
@@ -72,28 +273,88 @@ impl SolarConfig {
 pub struct Dependency {
     pub name: String,
     pub publisher: String,
-    pub version: String,
+    /// Version requirement, e.g. `^0.1`. See [`version`]
+    pub requirement: String,
     pub repo: Option<String>,
+    /// Directory of a local dependency, relative to the declaring project.
+    /// Local dependencies don't have a version requirement.
+    pub path: Option<String>,
 }
 
 impl Dependency {
-    pub fn basepath(&self) -> IdPath {
+    /// Unique Id of the library in the given version
+    pub fn basepath(&self, version: &str) -> IdPath {
         let name = &self.name;
         let publisher = &self.publisher;
-        let version = &self.version;
 
         vec![format!("{name}({publisher})"), version.to_owned()]
     }
 
-    /// Gives the directory of the library within the local filesystem.
-    pub fn dir(&self) -> String {
-        let path = get_solar_path();
-        let path = path + "libraries/";
+    /// e.g. `std(solar-lang)`, as written in the solar.yaml
+    pub fn key(&self) -> String {
+        format!("{}({})", self.name, self.publisher)
+    }
+
+    /// Gives the directory of the library in the given version within the local filesystem.
+    pub fn dir(&self, version: &str) -> String {
+        self.dir_in(&get_solar_path(), version)
+    }
+
+    /// Directory of the library in the given version within the given solar path.
+    pub fn dir_in(&self, solar_path: &str, version: &str) -> String {
+        let path = solar_path.to_string() + "libraries/";
+
+        path + &self.basepath(version).join("/")
+    }
+
+    /// Directory containing all installed versions of the library.
+    pub fn libraries_dir(&self) -> String {
+        format!("{}libraries/{}", get_solar_path(), self.key())
+    }
+
+    /// Directory of a local dependency,
+    /// resolved relative to the root of the project declaring it.
+    pub fn local_dir(&self, declaring_root: &str) -> anyhow::Result<Option<String>> {
+        let Some(path) = &self.path else {
+            return Ok(None);
+        };
+
+        // the root of a script is the file itself
+        let root = Path::new(declaring_root);
+        let root = if root.is_file() {
+            root.parent().unwrap_or(Path::new(""))
+        } else {
+            root
+        };
+
+        let dir = root.join(path);
+        let dir = dir.canonicalize().with_context(|| {
+            format!("local dependency {} not found at {}", self.name, dir.display())
+        })?;
 
-        path + &self.basepath().join("/")
+        Ok(Some(dir.to_str().expect("path to be utf-8").to_string()))
     }
 
-    fn from_key_value(key: &str, value: &str) -> Result<Self, String> {
+    fn from_key_value(key: &str, value: &DependencySpec) -> Result<Self, String> {
+        let value = match value {
+            DependencySpec::Version(value) => value,
+            DependencySpec::Local { path } => {
+                // the publisher is optional for local dependencies.
+                let (name, publisher) = match key.split_once('(') {
+                    Some((name, rest)) => (name, rest.trim_end_matches(')')),
+                    None => (key, ""),
+                };
+
+                return Ok(Self {
+                    name: name.to_string(),
+                    publisher: publisher.to_string(),
+                    requirement: String::new(),
+                    repo: None,
+                    path: Some(path.clone()),
+                });
+            }
+        };
+
         let Some((name, rest)) = key.split_once('(') else {
             return Err(format!(
                 "expect dependency keys to adhere to pattern '<name>(<publisher>)', but haven't found '(' in key '{key}'",
@@ -109,20 +370,34 @@ impl Dependency {
         let name = name.to_string();
 
         // the version may be written after declaration of a git repo.
-        let (repo, version) = if let Some((repo, version)) = value.rsplit_once('@') {
+        let (repo, requirement) = if let Some((repo, requirement)) = value.rsplit_once('@') {
             let repo = Some(repo.to_string());
-            let version = version.to_string();
+            let requirement = requirement.trim().to_string();
 
-            (repo, version)
+            (repo, requirement)
         } else {
-            (None, value.to_string())
+            (None, value.trim().to_string())
         };
 
+        // both are passed to git, where they must not be mistaken for options.
+        if let Some(repo) = repo.as_ref().filter(|repo| repo.starts_with('-')) {
+            return Err(format!(
+                "repository '{repo}' of dependency '{key}' must not start with '-'"
+            ));
+        }
+
+        if requirement.starts_with('-') {
+            return Err(format!(
+                "version '{requirement}' of dependency '{key}' must not start with '-'"
+            ));
+        }
+
         Ok(Self {
             name,
             publisher,
-            version,
+            requirement,
             repo,
+            path: None,
         })
     }
 }
@@ -1,4 +1,7 @@
+use super::GlobalModules;
+use crate::diagnostics::{Errors, Location};
 use crate::id::{IdItem, SymbolId};
+use crate::source_map::{FileId, Span};
 use crate::util::IdPath;
 use solar_parser::ast::import::Selection;
 use solar_parser::{ast, Ast};
@@ -91,6 +94,9 @@ impl<'a> Module<'a> {
                             }
                         }
                     }
+                    ast::body::BodyItem::BuildinTypeDecl(t) if t.name == symbol => {
+                        v.push((idmodule.to_vec(), idfile, IdItem::Type(iditem)));
+                    }
                     ast::body::BodyItem::Let(l) if l.identifier == symbol => {
                         v.push((idmodule.to_vec(), idfile, IdItem::GlobalVar(iditem)));
                     }
@@ -107,6 +113,41 @@ impl<'a> Module<'a> {
 
         Ok(v)
     }
+
+    /// Names of all symbols declared in this module,
+    /// including the methods derived from type declarations.
+    /// There is no notion of private symbols yet, so all of them are public.
+    pub fn symbols(&self) -> Vec<&'a str> {
+        let mut symbols = Vec::new();
+
+        for fileinfo in &self.files {
+            for item in &fileinfo.ast.items {
+                match item {
+                    ast::body::BodyItem::Function(f) => symbols.push(f.name.value),
+                    ast::body::BodyItem::TypeDecl(t) => {
+                        symbols.push(t.name.value);
+
+                        match &t.fields {
+                            ast::EnumOrStructFields::EnumFields(fields) => {
+                                symbols.extend(fields.iter().map(|f| f.name.value));
+                            }
+                            ast::EnumOrStructFields::StructFields(fields) => {
+                                symbols.extend(fields.iter().map(|f| f.name.value));
+                            }
+                        }
+                    }
+                    ast::body::BodyItem::BuildinTypeDecl(t) => symbols.push(t.name.value),
+                    ast::body::BodyItem::Let(l) => symbols.push(l.identifier.value),
+                    // Tests don't have names,
+                    ast::body::BodyItem::Test(_) => continue,
+                }
+            }
+        }
+
+        symbols.sort_unstable();
+        symbols.dedup();
+        symbols
+    }
 }
 
 #[derive(Debug, Clone, Error)]
@@ -133,6 +174,8 @@ impl std::fmt::Display for FindError {
 pub struct FileInfo<'a> {
     // NOTE this might be redundant
     pub filename: String,
+    /// The file inside the [`crate::source_map::SourceMap`], the content is borrowed from
+    pub source: FileId,
 
     /// Maps individual symbols (e.g. `length`) to paths,
     /// where they should be found in (e.g. std/0.0.1/string/).
@@ -143,17 +186,53 @@ pub struct FileInfo<'a> {
     /// is valid, expected
     /// and will require resolving from multiple locations.
     pub imports: SymbolResolver,
+    /// Modules, that all symbols get imported from.
+    /// e.g.
+    ///    use @std.io..
+    /// They are added to the imports by [`link_imports`],
+    /// once all modules are known.
+    pub wildcard_imports: Vec<IdPath>,
     pub ast: Ast<'a>,
+    /// Source code of the file.
+    /// All spans inside the AST are slices of it.
+    pub content: &'a str,
+}
+
+impl FileInfo<'_> {
+    /// Location of a span of the AST inside this file
+    pub fn locate(&self, span: &str) -> Option<Location> {
+        Location::from_span(&self.filename, self.content, self.span(span)?)
+    }
+
+    /// Position of a span of the AST, that doesn't borrow from the source code.
+    pub fn span(&self, span: &str) -> Option<Span> {
+        Span::within(self.source, self.content, span)
+    }
 }
 
 #[derive(Debug, Error)]
 pub enum ResolveError<'a> {
     LibNotInDeps {
-        // TODO include location...
-        // but how do I want to do it project wide?
         libname: String,
+        location: Option<Location>,
     },
     ParseErr(ast::NomErr<'a>),
+    /// Parse error, that doesn't borrow from the source code anymore
+    Syntax(String),
+}
+
+impl ResolveError<'_> {
+    /// Detaches the error from the source code,
+    /// so that it can be reported after the source code has been dropped.
+    pub fn into_owned(self) -> ResolveError<'static> {
+        match self {
+            ResolveError::LibNotInDeps { libname, location } => {
+                ResolveError::LibNotInDeps { libname, location }
+            }
+            ResolveError::ParseErr(e) => ResolveError::Syntax(e.to_string()),
+            ResolveError::Syntax(e) => ResolveError::Syntax(e),
+        }
+    }
 }
 
 impl<'a> From<ast::NomErr<'a>> for ResolveError<'a> {
@@ -165,11 +244,18 @@ impl<'a> From<ast::NomErr<'a>> for ResolveError<'a> {
 impl std::fmt::Display for ResolveError<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ResolveError::LibNotInDeps { libname } => write!(
-                f,
-                "imported libraries '{libname}' not found in dependencies"
-            ),
+            ResolveError::LibNotInDeps { libname, location } => {
+                write!(
+                    f,
+                    "imported libraries '{libname}' not found in dependencies"
+                )?;
+                if let Some(location) = location {
+                    write!(f, "\n{location}")?;
+                }
+                Ok(())
+            }
             ResolveError::ParseErr(e) => e.fmt(f),
+            ResolveError::Syntax(e) => write!(f, "{e}"),
         }
     }
 }
@@ -177,6 +263,7 @@ impl std::fmt::Display for ResolveError<'_> {
 impl<'a> FileInfo<'a> {
     pub fn from_code(
         filename: String,
+        source: FileId,
         depmap: &HashMap<String, IdPath>,
         basepath: &IdPath,
         content: &'a str,
@@ -185,23 +272,33 @@ impl<'a> FileInfo<'a> {
         let ast = Ast::from_source_code(content)?;
 
         // build up lookup table to resolve imported symbols.
-        let imports = resolve_imports(&ast, depmap, basepath)?;
+        let (imports, wildcard_imports) =
+            resolve_imports(&ast, depmap, basepath, &filename, source, content)?;
 
         Ok(FileInfo {
             filename,
+            source,
             imports,
+            wildcard_imports,
             ast,
+            content,
         })
     }
 }
 
 /// Resolve all imports from the ast to their global symbols for later lookup.
+/// Wildcard imports are returned separately,
+/// because the symbols of the imported module aren't known yet.
 fn resolve_imports<'a>(
     ast: &Ast<'a>,
     depmap: &HashMap<String, IdPath>,
     basepath: &IdPath,
-) -> Result<SymbolResolver, ResolveError<'a>> {
+    filename: &str,
+    source: FileId,
+    content: &str,
+) -> Result<(SymbolResolver, Vec<IdPath>), ResolveError<'a>> {
     let mut imports = HashMap::new();
+    let mut wildcard_imports = Vec::new();
 
     for import in ast.imports.iter() {
         // the ID path might be from a library, or from this project.
@@ -218,6 +315,8 @@ fn resolve_imports<'a>(
                 // if we can't find the symbol inside the dependencies, it's an error
                 .ok_or_else(|| ResolveError::LibNotInDeps {
                     libname: lib.to_string(),
+                    location: Span::within(source, content, import.path[0].span)
+                        .and_then(|span| Location::from_span(filename, content, span)),
                 })?;
 
             // append rest of the import path to the absolute path we just created
@@ -239,11 +338,9 @@ fn resolve_imports<'a>(
 
         match &import.items {
             Selection::All => {
-                unimplemented!("{}\n{}\n{}",
-                "found '..' selection.",
-                "Needs lookup for all symbols in a library.",
-                "Will need to happen eventually anyway, in order to check that every import is valid (and public)"
-            );
+                // e.g. use @std.io..
+                // the path is the module, all symbols get imported from.
+                wildcard_imports.push(path);
             }
             Selection::This => {
                 // the last symbol of the path was the concrete import item.
@@ -267,5 +364,169 @@ fn resolve_imports<'a>(
         }
     }
 
-    Ok(imports)
+    Ok((imports, wildcard_imports))
+}
+
+#[derive(Debug, Error)]
+pub enum ImportError {
+    /// The imported module doesn't exist
+    ModuleNotFound {
+        module: IdPath,
+        location: Option<Location>,
+    },
+    /// The imported module doesn't declare the symbol
+    SymbolNotFound {
+        symbol: String,
+        module: IdPath,
+        location: Option<Location>,
+    },
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let location = match self {
+            Self::ModuleNotFound { module, location } => {
+                write!(f, "imported module {} not found", module.join("."))?;
+                location
+            }
+            Self::SymbolNotFound {
+                symbol,
+                module,
+                location,
+            } => {
+                write!(
+                    f,
+                    "imported symbol {symbol} not found in module {}",
+                    module.join(".")
+                )?;
+                location
+            }
+        };
+
+        if let Some(location) = location {
+            write!(f, "\n{location}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Adds the symbols of wildcard imports to the imports of each file
+/// and checks, that all imported symbols exist.
+/// Requires all modules to be read, because imports may refer to any of them.
+/// All invalid imports are reported at once.
+pub fn link_imports(modules: &mut GlobalModules) -> Result<(), Errors<ImportError>> {
+    // (module, file, symbol, path of imported module)
+    let mut wildcard_symbols = Vec::new();
+    let mut errors = Vec::new();
+
+    // sorted, so that errors are reported in the same order every time
+    let mut idmodules = modules.keys().collect::<Vec<_>>();
+    idmodules.sort();
+
+    for idmodule in idmodules {
+        let module = &modules[idmodule];
+        for (idfile, fileinfo) in module.files.iter().enumerate() {
+            let mut imports = fileinfo.imports.iter().collect::<Vec<_>>();
+            imports.sort();
+
+            for (symbol, paths) in imports {
+                for path in paths {
+                    if let Err(e) = check_import(modules, symbol, path, fileinfo) {
+                        errors.push(e);
+                    }
+                }
+            }
+
+            for path in &fileinfo.wildcard_imports {
+                let Some(imported) = modules.get(path) else {
+                    errors.push(ImportError::ModuleNotFound {
+                        module: path.clone(),
+                        location: import_span(fileinfo, path.last().map(String::as_str))
+                            .and_then(|span| fileinfo.locate(span)),
+                    });
+                    continue;
+                };
+
+                for symbol in imported.symbols() {
+                    let symbol = symbol.to_string();
+                    wildcard_symbols.push((idmodule.clone(), idfile, symbol, path.clone()));
+                }
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(Errors(errors));
+    }
+
+    for (idmodule, idfile, symbol, path) in wildcard_symbols {
+        let module = modules.get_mut(&idmodule).expect("module to be read");
+        module.files[idfile]
+            .imports
+            .entry(symbol)
+            .or_insert_with(Vec::new)
+            .push(path);
+    }
+
+    Ok(())
+}
+
+/// Checks, that the symbol is declared in the imported module.
+/// The symbol may also be a submodule itself, e.g. `use @std.io`
+fn check_import(
+    modules: &GlobalModules,
+    symbol: &str,
+    path: &IdPath,
+    fileinfo: &FileInfo,
+) -> Result<(), ImportError> {
+    let submodule = path
+        .iter()
+        .cloned()
+        .chain(std::iter::once(symbol.to_string()))
+        .collect::<IdPath>();
+    if modules.contains_key(&submodule) {
+        return Ok(());
+    }
+
+    let location = import_span(fileinfo, Some(symbol)).and_then(|span| fileinfo.locate(span));
+
+    let Some(module) = modules.get(path) else {
+        return Err(ImportError::ModuleNotFound {
+            module: path.clone(),
+            location,
+        });
+    };
+
+    if module.find(symbol, path).is_err() {
+        return Err(ImportError::SymbolNotFound {
+            symbol: symbol.to_string(),
+            module: path.clone(),
+            location,
+        });
+    }
+
+    Ok(())
+}
+
+/// Span of the imported symbol (or the last segment of the imported path) within the file,
+/// e.g. `redline` in `use @std.io.(println, redline)`
+fn import_span<'a>(fileinfo: &FileInfo<'a>, symbol: Option<&str>) -> Option<&'a str> {
+    let symbol = symbol?;
+
+    for import in &fileinfo.ast.imports {
+        if let Selection::Items(items) = &import.items {
+            if let Some(item) = items.iter().find(|item| item.value == symbol) {
+                return Some(item.span);
+            }
+        }
+
+        if let Some(last) = import.path.last() {
+            if last.value == symbol {
+                return Some(last.span);
+            }
+        }
+    }
+
+    None
 }
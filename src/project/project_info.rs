@@ -1,59 +1,477 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
 
 use anyhow::Context;
 use hotel::HotelMap;
+use rayon::prelude::*;
+use thiserror::Error;
 
-use crate::util::{self, IdPath};
+use crate::{
+    diagnostics::Errors,
+    source_map::SourceMap,
+    util::{self, IdPath},
+};
 
-use super::{Module, Project};
+use super::{
+    check_lockfile, link_imports, resolve_locked, ConflictPolicy, Dependency, Lockfile, Module,
+    Project, SolarConfig,
+};
 
 pub type ProjectInfo = HotelMap<IdPath, Project>;
 
+/// Reads the target project and all its dependencies.
+/// The target may also be a single .sol file, which is wrapped in an implicit project.
 pub fn read_all_projects(fsroot: &str) -> anyhow::Result<ProjectInfo> {
+    read_target(fsroot, false)
+}
+
+/// Reads the target project and all its dependencies, including the dev-dependencies.
+/// The solar.lock is checked, but not updated, because it only contains the regular dependencies.
+pub fn read_all_projects_for_tests(fsroot: &str) -> anyhow::Result<ProjectInfo> {
+    read_target(fsroot, true)
+}
+
+fn read_target(fsroot: &str, dev: bool) -> anyhow::Result<ProjectInfo> {
+    if fsroot.ends_with(".sol") && Path::new(fsroot).is_file() {
+        let p = Project::script(fsroot, util::target_id());
+        return read_dependencies(p, None);
+    }
+
+    let mut p = Project::open(fsroot, util::target_id())?;
+    if dev {
+        p.config.include_dev_dependencies();
+    }
+
+    // scripts don't have a place for a lockfile
+    let locked = Lockfile::find(fsroot)?;
+    let projects = read_dependencies(p, locked.as_ref())?;
+
+    check_lockfile(fsroot, locked.as_ref(), &projects, !dev)?;
+
+    Ok(projects)
+}
+
+/// Reads the dependencies of an implicit target project without any files, e.g. for the REPL.
+pub fn read_empty_project(name: &str) -> anyhow::Result<ProjectInfo> {
+    let p = Project::empty(name, util::target_id());
+
+    read_dependencies(p, None)
+}
+
+/// Collects the project and all its (transitive) dependencies.
+/// The version requirements of the dependencies are resolved along the way,
+/// preferring the versions locked in the solar.lock of the target project.
+/// Multiple versions of the same library are reported, as configured by the target project.
+fn read_dependencies(p: Project, locked: Option<&Lockfile>) -> anyhow::Result<ProjectInfo> {
     let mut projects = HotelMap::new();
-    let p = Project::open(fsroot, util::target_id())?;
+    let policy = p.config.version_conflicts;
+
+    /// The chain contains the projects, that are currently being read,
+    /// starting with the target project.
+    fn insert_all(
+        mut p: Project,
+        projects: &mut ProjectInfo,
+        locked: Option<&Lockfile>,
+        chain: &mut Vec<IdPath>,
+    ) -> anyhow::Result<()> {
+        chain.push(p.basepath.clone());
 
-    fn insert_all(p: Project, projects: &mut HotelMap<IdPath, Project>) -> anyhow::Result<()> {
         for dep in p.config.deps() {
-            let path = dep.basepath();
+            let (path, dir, config) = match dep.local_dir(&p.fsroot)? {
+                Some(dir) => {
+                    let config = SolarConfig::read(&format!("{dir}/solar.yaml"))
+                        .with_context(|| format!("reading local dependency {}", dep.name))?;
+
+                    (config.local_basepath(&dir), dir, Some(config))
+                }
+                None => {
+                    let version = resolve_version(&dep, projects, locked)?;
+
+                    (dep.basepath(&version), dep.dir(&version), None)
+                }
+            };
+
+            p.dep_map.insert(dep.name.clone(), path.clone());
+
+            // The project isn't inserted, until all its dependencies are read.
+            // So it needs to be checked against the chain, to not recurse forever.
+            if let Some(start) = chain.iter().position(|basepath| *basepath == path) {
+                let mut cycle = chain[start..].to_vec();
+                cycle.push(path);
+                return Err(DependencyError::Cycle(cycle).into());
+            }
+
             // skip project, if we have already read it.
             if projects.contains(&path) {
                 continue;
             }
 
-            let dir = dep.dir();
-            let p = Project::open(&dir, path)?;
-            insert_all(p, projects)?;
+            let p = match config {
+                Some(config) => Project::new(&dir, path, config),
+                None => Project::open(&dir, path)?,
+            };
+            insert_all(p, projects, locked, chain)?;
         }
 
+        chain.pop();
         projects.insert(p.basepath.clone(), p);
 
         Ok(())
     }
 
-    insert_all(p, &mut projects)?;
+    insert_all(p, &mut projects, locked, &mut Vec::new())?;
+
+    let conflicts = version_conflicts(&projects);
+    match policy {
+        ConflictPolicy::Allow => {}
+        ConflictPolicy::Warn => {
+            for conflict in conflicts {
+                eprintln!("warning: {conflict}");
+            }
+        }
+        ConflictPolicy::Error if !conflicts.is_empty() => return Err(Errors(conflicts).into()),
+        ConflictPolicy::Error => {}
+    }
 
     Ok(projects)
 }
 
+/// Resolves the version requirement of the dependency.
+/// A version, that has been chosen for another dependent before, is preferred,
+/// so that the library is only loaded once, if possible.
+/// Otherwise the locked version is used, and only then the highest installed one.
+fn resolve_version(
+    dep: &Dependency,
+    projects: &ProjectInfo,
+    locked: Option<&Lockfile>,
+) -> anyhow::Result<String> {
+    let loaded = projects
+        .keys()
+        .filter(|basepath| basepath[0] == dep.key())
+        .map(|basepath| basepath[1].clone())
+        .collect::<Vec<_>>();
+
+    if let Ok(version) = dep.resolve_among(&loaded) {
+        return Ok(version);
+    }
+
+    if let Some(locked) = locked {
+        if let Some(version) = resolve_locked(dep, locked)? {
+            return Ok(version);
+        }
+    }
+
+    Ok(dep.resolve()?)
+}
+
+/// Libraries, that are used in multiple versions,
+/// e.g. because two dependents require versions, that don't overlap.
+fn version_conflicts(projects: &ProjectInfo) -> Vec<DependencyError> {
+    // library => version => dependents
+    let mut libraries: BTreeMap<&str, BTreeMap<&str, Vec<String>>> = BTreeMap::new();
+
+    for (dependent, _, project) in projects.iter() {
+        for basepath in project.dep_map.values() {
+            libraries
+                .entry(basepath[0].as_str())
+                .or_default()
+                .entry(basepath[1].as_str())
+                .or_default()
+                .push(dependent.join(" "));
+        }
+    }
+
+    libraries
+        .into_iter()
+        .filter(|(_, versions)| versions.len() > 1)
+        .map(|(library, versions)| DependencyError::Conflict {
+            library: library.to_string(),
+            versions: versions
+                .into_iter()
+                .map(|(version, mut dependents)| {
+                    dependents.sort();
+                    (version.to_string(), dependents)
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+#[derive(Debug, Error)]
+pub enum DependencyError {
+    /// Libraries depend on each other, e.g. a -> b -> a
+    Cycle(Vec<IdPath>),
+    /// The same library is used in multiple versions
+    Conflict {
+        library: String,
+        /// version, along with the projects depending on it
+        versions: Vec<(String, Vec<String>)>,
+    },
+}
+
+impl std::fmt::Display for DependencyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cycle(cycle) => {
+                let cycle = cycle
+                    .iter()
+                    .map(|basepath| basepath.join(" "))
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+                write!(f, "cyclic dependency: {cycle}")
+            }
+            Self::Conflict { library, versions } => {
+                let versions = versions
+                    .iter()
+                    .map(|(version, dependents)| {
+                        format!("{version} (required by {})", dependents.join(", "))
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "multiple versions of library {library} are used: {versions}")
+            }
+        }
+    }
+}
+
 /// Mapping from IdPaths/ModulePaths (use @std.0.1.0.types.string.String) to all modules.
 /// ASTs can be found inside the modules.
 pub type GlobalModules<'a> = HashMap<IdPath, Module<'a>>;
 
+/// Loads the source code of all files of all projects.
+/// The directories are walked and the files are read in parallel, but added in a fixed order,
+/// so that the [`crate::source_map::FileId`] of each file is the same on every run.
+/// Files, that can't be read, are collected, so that all of them can be reported at once.
+pub fn read_sources(projects: &ProjectInfo) -> anyhow::Result<SourceMap> {
+    let projects = projects.iter_values().collect::<Vec<_>>();
+
+    // collecting keeps the order of the projects and their files
+    let files = projects
+        .into_par_iter()
+        .flat_map_iter(|(project_id, project)| {
+            project
+                .source_files()
+                .into_iter()
+                .map(move |path| (project_id, path))
+        })
+        .collect::<Vec<_>>();
+
+    let contents = files
+        .par_iter()
+        .map(|(_, path)| {
+            std::fs::read_to_string(path)
+                .with_context(|| format!("reading file {}", path.display()))
+        })
+        .collect::<Vec<_>>();
+
+    let mut sources = SourceMap::new();
+    let mut errors = Vec::new();
+    for ((project_id, path), content) in files.into_iter().zip(contents) {
+        match content {
+            Ok(content) => {
+                let path = path.to_str().expect("path to be utf-8").to_string();
+                sources.add(path, content, Some(project_id));
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(Errors(errors).into());
+    }
+
+    Ok(sources)
+}
+
 /// create global mapping of ModulePaths to Modules
-/// i.e. across all dependencies and sub-dependencies
-pub fn read_modules(projects: &ProjectInfo) -> anyhow::Result<GlobalModules<'_>> {
-    let mut modules = HashMap::new();
+/// i.e. across all dependencies and sub-dependencies.
+/// The modules borrow from the source code, see [`read_sources`].
+pub fn read_modules<'a>(
+    projects: &'a ProjectInfo,
+    sources: &'a SourceMap,
+) -> anyhow::Result<GlobalModules<'a>> {
+    let mut modules = read_unlinked_modules(projects, sources)?;
+
+    link_imports(&mut modules)?;
+
+    Ok(modules)
+}
 
-    for (project_id, project) in projects.iter_values() {
-        let symbol_table = project
-            .read_all(project_id)
-            .context(format!("reading project {}", project.fsroot))?;
+/// Parses all files of the projects inside the source map into modules,
+/// without resolving wildcard imports.
+/// Allows adding files, that aren't part of any project, before calling [`link_imports`].
+///
+/// The files are parsed in parallel.
+/// They are added to their modules in the order of the source map though,
+/// so that the [`crate::id::IdFile`] of each file is the same on every run.
+/// Errors in individual files are collected, so that all of them can be reported at once.
+pub fn read_unlinked_modules<'a>(
+    projects: &'a ProjectInfo,
+    sources: &'a SourceMap,
+) -> anyhow::Result<GlobalModules<'a>> {
+    let files = sources
+        .iter()
+        .filter_map(|(id, source)| Some((id, source, source.project?)))
+        .collect::<Vec<_>>();
 
-        for (sym, path) in symbol_table.into_iter() {
-            modules.insert(sym, path);
+    // collecting keeps the order of the files
+    let parsed = files
+        .into_par_iter()
+        .map(|(id, source, project_id)| {
+            let project = projects
+                .get_by_index(project_id)
+                .expect("project of the file to be read");
+            let file = project
+                .parse_file(id, source)
+                .with_context(|| format!("reading project {}", project.fsroot))?;
+
+            Ok((project_id, file))
+        })
+        .collect::<Vec<anyhow::Result<_>>>();
+
+    let mut modules: GlobalModules = HashMap::new();
+    let mut errors = Vec::new();
+    for result in parsed {
+        match result {
+            Ok((project_id, (idmodule, fileinfo))) => modules
+                .entry(idmodule)
+                .or_insert(Module::new(project_id))
+                .add_file(fileinfo),
+            Err(e) => errors.push(e),
         }
     }
 
+    if !errors.is_empty() {
+        return Err(Errors(errors).into());
+    }
+
     Ok(modules)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::*;
+
+    /// Directory with one project per entry, depending on each other by path.
+    fn workspace(test: &str, projects: &[(&str, &str, &[(&str, &str)])]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("solar-deps-{}-{test}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        for (dir, name, deps) in projects {
+            let mut yaml = format!("name: {name}\nversion: 0.1.0\n");
+            if *dir == "app" {
+                yaml += "version-conflicts: error\n";
+            }
+            if !deps.is_empty() {
+                yaml += "dependencies:\n";
+            }
+            for (key, path) in deps.iter() {
+                yaml += &format!("  {key}: {{ path: ../{path} }}\n");
+            }
+
+            fs::create_dir_all(root.join(dir)).unwrap();
+            fs::write(root.join(dir).join("solar.yaml"), yaml).unwrap();
+        }
+
+        root
+    }
+
+    fn read(root: &Path) -> anyhow::Result<ProjectInfo> {
+        let app = root.join("app");
+        let p = Project::open(app.to_str().unwrap(), util::target_id())?;
+
+        read_dependencies(p, None)
+    }
+
+    #[test]
+    fn detects_cycles() {
+        let root = workspace(
+            "cycle",
+            &[
+                ("app", "app", &[("a", "a")]),
+                ("a", "a", &[("b", "b")]),
+                ("b", "b", &[("a", "a")]),
+            ],
+        );
+
+        let error = read(&root).unwrap_err();
+        let Some(DependencyError::Cycle(cycle)) = error.downcast_ref() else {
+            panic!("expected a cycle, got {error}");
+        };
+
+        let names = cycle.iter().map(|p| p[0].as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["a()", "b()", "a()"]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn reports_libraries_used_in_multiple_versions() {
+        let root = workspace(
+            "conflict",
+            &[
+                ("app", "app", &[("a", "a"), ("b", "b")]),
+                ("a", "a", &[("shared", "shared1")]),
+                ("b", "b", &[("shared", "shared2")]),
+                ("shared1", "shared", &[]),
+                ("shared2", "shared", &[]),
+            ],
+        );
+
+        let error = read(&root).unwrap_err();
+        let Some(Errors(conflicts)) = error.downcast_ref::<Errors<DependencyError>>() else {
+            panic!("expected conflicts, got {error}");
+        };
+
+        let [DependencyError::Conflict { library, versions }] = conflicts.as_slice() else {
+            panic!("expected one conflict, got {conflicts:?}");
+        };
+        assert_eq!(library, "shared()");
+        assert_eq!(versions.len(), 2);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn shares_libraries_used_by_multiple_dependents() {
+        let root = workspace(
+            "shared",
+            &[
+                ("app", "app", &[("a", "a"), ("b", "b")]),
+                ("a", "a", &[("shared", "shared")]),
+                ("b", "b", &[("shared", "shared")]),
+                ("shared", "shared", &[]),
+            ],
+        );
+
+        let projects = read(&root).unwrap();
+        assert_eq!(projects.keys().count(), 4);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn reports_all_unreadable_files() {
+        let root = workspace("unreadable", &[("app", "app", &[])]);
+        // not valid utf-8
+        fs::write(root.join("app/b.sol"), [0xff, 0xfe]).unwrap();
+        fs::write(root.join("app/a.sol"), [0xff, 0xfe]).unwrap();
+
+        let projects = read(&root).unwrap();
+        let error = read_sources(&projects).unwrap_err();
+        let Some(Errors(errors)) = error.downcast_ref::<Errors<anyhow::Error>>() else {
+            panic!("expected multiple errors, got {error}");
+        };
+
+        let files = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(files.len(), 2);
+        assert!(files[0].ends_with("a.sol"), "{files:?}");
+        assert!(files[1].ends_with("b.sol"), "{files:?}");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
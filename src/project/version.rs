@@ -0,0 +1,178 @@
+//! Version requirements of dependencies
+//!
+//! e.g.
+//!     std(solar-lang): ^0.1
+//!     mylib(someone): >=1.2, <2
+//!
+//! The syntax is the one of cargo.
+//! A plain version like `0.1.0` is a caret requirement (`^0.1.0`),
+//! an exact version needs to be written as `=0.1.0`.
+//! Requirements are resolved to the highest compatible version,
+//! that is installed in the solar path.
+
+use semver::{Version, VersionReq};
+use thiserror::Error;
+
+use super::{get_solar_path, Dependency};
+
+#[derive(Debug, Error)]
+pub enum VersionError {
+    InvalidRequirement {
+        dependency: String,
+        requirement: String,
+        error: semver::Error,
+    },
+    /// None of the available versions satisfies the requirement
+    NoMatch {
+        dependency: String,
+        requirement: String,
+        available: Vec<String>,
+    },
+}
+
+impl std::fmt::Display for VersionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidRequirement {
+                dependency,
+                requirement,
+                error,
+            } => write!(
+                f,
+                "invalid version requirement '{requirement}' of dependency {dependency}: {error}"
+            ),
+            Self::NoMatch {
+                dependency,
+                requirement,
+                available,
+            } if available.is_empty() => write!(
+                f,
+                "no version of dependency {dependency} matching '{requirement}' found. No versions are installed in {}libraries. Run `solar fetch` to download it",
+                get_solar_path()
+            ),
+            Self::NoMatch {
+                dependency,
+                requirement,
+                available,
+            } => write!(
+                f,
+                "no version of dependency {dependency} matching '{requirement}' found. Available versions: {}",
+                available.join(", ")
+            ),
+        }
+    }
+}
+
+impl Dependency {
+    /// Highest installed version, that satisfies the requirement.
+    pub fn resolve(&self) -> Result<String, VersionError> {
+        self.resolve_among(&self.installed_versions())
+    }
+
+    /// Highest of the given versions, that satisfies the requirement.
+    /// Versions, that aren't valid semantic versions, are ignored.
+    pub fn resolve_among(&self, versions: &[String]) -> Result<String, VersionError> {
+        let requirement = self.parse_requirement()?;
+
+        versions
+            .iter()
+            .filter_map(|v| Some((Version::parse(v).ok()?, v)))
+            .filter(|(version, _)| requirement.matches(version))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, v)| v.clone())
+            .ok_or_else(|| VersionError::NoMatch {
+                dependency: self.key(),
+                requirement: self.requirement.clone(),
+                available: versions.to_vec(),
+            })
+    }
+
+    /// Versions of the library, that are installed in the solar path, lowest first.
+    pub fn installed_versions(&self) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(self.libraries_dir()) else {
+            return Vec::new();
+        };
+
+        let mut versions = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+            .filter_map(|name| Some((Version::parse(&name).ok()?, name)))
+            .collect::<Vec<_>>();
+        versions.sort();
+
+        versions.into_iter().map(|(_, name)| name).collect()
+    }
+
+    pub(super) fn parse_requirement(&self) -> Result<VersionReq, VersionError> {
+        VersionReq::parse(&self.requirement).map_err(|error| VersionError::InvalidRequirement {
+            dependency: self.key(),
+            requirement: self.requirement.clone(),
+            error,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::DependencySpec;
+
+    fn dependency(requirement: &str) -> Dependency {
+        let value = DependencySpec::Version(requirement.to_string());
+        Dependency::from_key_value("mylib(someone)", &value).unwrap()
+    }
+
+    fn versions(versions: &[&str]) -> Vec<String> {
+        versions.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn resolves_to_highest_matching_version() {
+        let available = versions(&["0.1.0", "0.1.3", "0.2.0", "1.0.0"]);
+
+        let resolve = |requirement| dependency(requirement).resolve_among(&available).unwrap();
+        assert_eq!(resolve("^0.1"), "0.1.3");
+        assert_eq!(resolve(">=0.1, <1"), "0.2.0");
+        assert_eq!(resolve("*"), "1.0.0");
+    }
+
+    #[test]
+    fn plain_versions_are_caret_requirements() {
+        let available = versions(&["0.1.0", "0.1.2", "0.2.0"]);
+
+        let resolve = |requirement| dependency(requirement).resolve_among(&available).unwrap();
+        assert_eq!(resolve("0.1.0"), "0.1.2");
+        assert_eq!(resolve("=0.1.0"), "0.1.0");
+    }
+
+    #[test]
+    fn ignores_invalid_versions() {
+        let available = versions(&["latest", "0.1.0", "0.1"]);
+
+        assert_eq!(
+            dependency("^0.1").resolve_among(&available).unwrap(),
+            "0.1.0"
+        );
+    }
+
+    #[test]
+    fn reports_available_versions_without_match() {
+        let available = versions(&["0.1.0", "0.2.0"]);
+
+        let result = dependency("^1").resolve_among(&available);
+        assert!(
+            matches!(result, Err(VersionError::NoMatch { available: a, .. }) if a == available)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_requirements() {
+        let result = dependency("not a version").resolve_among(&[]);
+
+        assert!(matches!(
+            result,
+            Err(VersionError::InvalidRequirement { .. })
+        ));
+    }
+}
@@ -0,0 +1,440 @@
+//! Interactive evaluation of solar code.
+//!
+//! e.g.
+//! ```text
+//! solar> use @std.types.int.(Int, add)
+//! solar> fun double(n: Int) -> Int = add n n
+//! solar> double 21
+//! (Int) 42
+//! solar> :type it
+//! Int
+//! ```
+//!
+//! Definitions (functions, types, global variables and imports) become part of a file
+//! inside the root module of the target project.
+//! Since the [`CompilerContext`] can't be altered once it's been created,
+//! it is rebuilt whenever the definitions change, along with the [`SourceMap`] it borrows from.
+//! Until then, it is kept, along with the scope of the session.
+//! If the modules can't be built after a change, e.g. because of an invalid import,
+//! the change is undone and the context is built from the previous definitions again.
+//! `it` is carried over to the rebuilt context, if its type is a buildin type.
+//! Values of other types are dropped, because the ids of their types may change.
+
+use std::io::{self, BufRead, Write};
+
+use anyhow::Context;
+use solar_parser::{ast::body::BodyItem, Ast};
+
+use crate::{
+    compilation::CompilerContext,
+    id::{IdFile, TypeId},
+    mir::StaticExpression,
+    project::{
+        link_imports, read_sources, read_unlinked_modules, FileInfo, GlobalModules, Module,
+        ProjectInfo, ResolveError,
+    },
+    source_map::SourceMap,
+    util::{self, Scope},
+    value::Value,
+};
+
+/// Name of the file, that contains the definitions typed into the REPL.
+const REPL_FILE: &str = "<repl>";
+
+const HELP: &str = "\
+Enter an expression to evaluate it, or a definition (fun, type, let, use) to add it to the session.
+The value of the last expression is available as `it`.
+
+  :type <expr>   print the type of an expression
+  :mir <expr>    print the compiled expression
+  :load <file>   add the definitions of a file to the session
+  :{ .. :}       enter multiple lines at once
+  :help          print this help
+  :quit          exit the REPL";
+
+/// Runs the REPL on stdin and stdout, until the input ends or `:quit` is entered.
+/// The target project of the project info is loaded into the session.
+pub fn run(project_info: &ProjectInfo) -> anyhow::Result<()> {
+    let mut session = Session::default();
+    // definitions before the last change, in case it can't be built
+    let mut previous: Option<Session> = None;
+    let mut input = Input::default();
+
+    println!("solar REPL. Enter :help for help.");
+
+    // type name and value of `it`, while the context is rebuilt
+    let mut it = None;
+
+    loop {
+        let sources = session.sources(project_info)?;
+        let (modules, idfile) = match session.modules(project_info, &sources) {
+            Ok(built) => built,
+            Err(e) => match previous.take() {
+                Some(valid) => {
+                    eprintln!("error: {e:#}");
+                    session = valid;
+                    continue;
+                }
+                None => return Err(e),
+            },
+        };
+        let ctx = CompilerContext::with_default_io(project_info, modules);
+
+        // values of the variables inside the scope, e.g. `it`
+        let mut scope = Scope::new();
+        let mut frame = Vec::new();
+
+        if let Some((name, value)) = it.take() {
+            let ty = ctx
+                .buildin_types
+                .by_name(&name)
+                .expect("only values of buildin types to be carried over");
+            scope.push("it", ty as TypeId);
+            frame.push(value);
+        }
+
+        // The context is kept, until the definitions change.
+        loop {
+            let Some(line) = input.read()? else {
+                return Ok(());
+            };
+
+            // on changes of the definitions, the session as it was before
+            let changed = match Command::parse(&line) {
+                Command::Empty => continue,
+                Command::Quit => return Ok(()),
+                Command::Help => {
+                    println!("{HELP}");
+                    continue;
+                }
+                Command::Unknown(command) => {
+                    eprintln!("error: unknown command {command}. Enter :help for help.");
+                    continue;
+                }
+                Command::Type(source) => {
+                    match compile(&ctx, idfile, source, &scope) {
+                        Ok(expr) => println!("{}", ctx.type_name(expr.ty)),
+                        Err(e) => eprintln!("error: {e:#}"),
+                    }
+                    continue;
+                }
+                Command::Mir(source) => {
+                    match compile(&ctx, idfile, source, &scope) {
+                        Ok(expr) => println!("{expr:#?}"),
+                        Err(e) => eprintln!("error: {e:#}"),
+                    }
+                    continue;
+                }
+                Command::Load(path) => {
+                    let before = session.clone();
+                    session.load(path).map(|()| before)
+                }
+                Command::Code(source) => match definitions(source) {
+                    Some(names) => {
+                        let before = session.clone();
+                        session.define(source, names);
+                        Ok(before)
+                    }
+                    None => {
+                        if let Err(e) = evaluate(&ctx, idfile, source, &mut scope, &mut frame) {
+                            eprintln!("error: {e:#}");
+                        }
+                        continue;
+                    }
+                },
+            };
+
+            match changed {
+                Ok(before) => {
+                    previous = Some(before);
+                    it = carry_it(&ctx, &scope, &frame);
+                    break;
+                }
+                Err(e) => eprintln!("error: {e:#}"),
+            }
+        }
+    }
+}
+
+/// Compiles an expression, that may refer to the variables of the scope.
+/// Let bindings inside the expression don't outlive it,
+/// so their slots are reused by the next expression.
+fn compile<'a>(
+    ctx: &'a CompilerContext<'a>,
+    idfile: IdFile,
+    source: &str,
+    scope: &Scope,
+) -> anyhow::Result<StaticExpression> {
+    // The expression is parsed as value of a global variable,
+    // because the parser only accepts whole files.
+    let content = format!("let it = {source}");
+    let ast = Ast::from_source_code(&content).map_err(|e| ResolveError::from(e).into_owned())?;
+
+    let Some(BodyItem::Let(var)) = ast.items.first() else {
+        anyhow::bail!("expected an expression");
+    };
+
+    let expr = ctx.compile_expression(&var.value, idfile, &content, &mut scope.clone())?;
+
+    Ok(expr)
+}
+
+/// Evaluates an expression and prints its value, which is bound to `it` afterwards.
+fn evaluate<'a>(
+    ctx: &'a CompilerContext<'a>,
+    idfile: IdFile,
+    source: &str,
+    scope: &mut Scope,
+    frame: &mut Vec<Value>,
+) -> anyhow::Result<()> {
+    let expr = compile(ctx, idfile, source, scope)?;
+    let value = ctx.evaluate(&expr, frame)?;
+
+    // output of the solar code might not have ended with a line break
+    io::stdout().flush()?;
+
+    if let Value::Void = value {
+        return Ok(());
+    }
+
+    println!("({}) {}", value.type_as_str(), ctx.display_value(&value));
+
+    // `it` keeps its slot, so the frame doesn't grow with every evaluation
+    let index = scope.set("it", expr.ty) as usize;
+    if frame.len() <= index {
+        frame.resize(index + 1, Value::Void);
+    }
+    frame[index] = value;
+
+    Ok(())
+}
+
+/// Type name and value of `it`, if it can be carried over to a rebuilt context.
+/// Only buildin types keep their ids, when the context is rebuilt.
+fn carry_it(ctx: &CompilerContext, scope: &Scope, frame: &[Value]) -> Option<(String, Value)> {
+    let (ty, index) = scope.get("it")?;
+    let name = ctx.type_name(ty);
+
+    if ctx.buildin_types.by_name(&name).map(|id| id as TypeId) != Some(ty) {
+        eprintln!("note: `it` has been reset, because its type {name} may have changed");
+        return None;
+    }
+
+    Some((name, frame[index as usize].clone()))
+}
+
+/// Names of the items, if the source consists of definitions only.
+/// Returns None for expressions.
+fn definitions(source: &str) -> Option<Vec<String>> {
+    let ast = Ast::from_source_code(source).ok()?;
+
+    if ast.items.is_empty() && ast.imports.is_empty() {
+        return None;
+    }
+
+    let names = ast
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            BodyItem::Function(f) => Some(f.name.value),
+            BodyItem::TypeDecl(t) => Some(t.name.value),
+            BodyItem::BuildinTypeDecl(t) => Some(t.name.value),
+            BodyItem::Let(l) => Some(l.identifier.value),
+            // Tests don't have names,
+            BodyItem::Test(_) => None,
+        })
+        .map(str::to_string)
+        .collect();
+
+    Some(names)
+}
+
+/// Source code, that has been added during the session.
+#[derive(Default, Clone)]
+struct Session {
+    /// Inputs containing definitions, along with the names they define
+    definitions: Vec<(String, Vec<String>)>,
+    /// Files added by `:load`, (path, content)
+    loaded: Vec<(String, String)>,
+}
+
+impl Session {
+    /// Adds definitions to the session.
+    /// Earlier inputs are replaced, if all the names they define are defined again.
+    fn define(&mut self, source: &str, names: Vec<String>) {
+        self.definitions.retain(|(other, defined)| {
+            let replaced = if names.is_empty() {
+                // imports are only added once
+                other == source
+            } else {
+                !defined.is_empty() && defined.iter().all(|name| names.contains(name))
+            };
+
+            !replaced
+        });
+        self.definitions.push((source.to_string(), names));
+    }
+
+    /// Adds the content of a file to the session.
+    /// Loading a file again replaces its previous content.
+    fn load(&mut self, path: &str) -> anyhow::Result<()> {
+        let content =
+            std::fs::read_to_string(path).with_context(|| format!("reading file {path}"))?;
+
+        self.loaded.retain(|(other, _)| other != path);
+        self.loaded.push((path.to_string(), content));
+
+        Ok(())
+    }
+
+    /// Loads the source code of all projects, followed by the files of the session.
+    /// The definitions typed into the REPL are the last file.
+    fn sources(&self, project_info: &ProjectInfo) -> anyhow::Result<SourceMap> {
+        let mut sources = read_sources(project_info)?;
+
+        for (path, content) in &self.loaded {
+            sources.add(path.clone(), content.clone(), None);
+        }
+
+        let definitions = self
+            .definitions
+            .iter()
+            .map(|(source, _)| source.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        sources.add(REPL_FILE.to_string(), definitions, None);
+
+        Ok(sources)
+    }
+
+    /// Reads all modules of the projects
+    /// and adds the files of the session to the root module of the target project.
+    /// Returns the modules and the file containing the definitions typed into the REPL.
+    fn modules<'p>(
+        &self,
+        project_info: &'p ProjectInfo,
+        sources: &'p SourceMap,
+    ) -> anyhow::Result<(GlobalModules<'p>, IdFile)> {
+        let mut modules = read_unlinked_modules(project_info, sources)?;
+
+        let target = util::target_id();
+        let (project_id, project) = project_info
+            .get_by_key(&target)
+            .expect("target project to be read");
+
+        let module = modules
+            .entry(target)
+            .or_insert_with(|| Module::new(project_id));
+
+        // The files of the session don't belong to any project.
+        // The definitions typed into the REPL come last.
+        let session_files = sources
+            .iter()
+            .filter(|(_, source)| source.project.is_none());
+        for (id, source) in session_files {
+            let fileinfo = FileInfo::from_code(
+                source.path.clone(),
+                id,
+                &project.dep_map,
+                &project.basepath,
+                &source.content,
+            )
+            .map_err(ResolveError::into_owned)
+            .with_context(|| format!("loading file {}", source.path))?;
+            module.add_file(fileinfo);
+        }
+        let idfile = (module.files.len() - 1) as IdFile;
+
+        link_imports(&mut modules)?;
+
+        Ok((modules, idfile))
+    }
+}
+
+/// A single input of the user
+enum Command<'s> {
+    Empty,
+    Quit,
+    Help,
+    Type(&'s str),
+    Mir(&'s str),
+    Load(&'s str),
+    Unknown(&'s str),
+    /// Either definitions or an expression
+    Code(&'s str),
+}
+
+impl<'s> Command<'s> {
+    fn parse(input: &'s str) -> Self {
+        let input = input.trim();
+
+        if input.is_empty() {
+            return Command::Empty;
+        }
+
+        let Some(command) = input.strip_prefix(':') else {
+            return Command::Code(input);
+        };
+
+        let (command, argument) = command
+            .split_once(char::is_whitespace)
+            .map(|(command, argument)| (command, argument.trim()))
+            .unwrap_or((command, ""));
+
+        match command {
+            "q" | "quit" => Command::Quit,
+            "h" | "help" => Command::Help,
+            "t" | "type" => Command::Type(argument),
+            "mir" => Command::Mir(argument),
+            "l" | "load" => Command::Load(argument),
+            _ => Command::Unknown(input),
+        }
+    }
+}
+
+/// Reads the input line by line.
+/// Lines between `:{` and `:}` are joined into a single input.
+#[derive(Default)]
+struct Input {
+    line: String,
+}
+
+impl Input {
+    /// Returns None, once the input has ended.
+    fn read(&mut self) -> io::Result<Option<String>> {
+        let Some(line) = self.read_line("solar> ")? else {
+            return Ok(None);
+        };
+
+        if line.trim() != ":{" {
+            return Ok(Some(line));
+        }
+
+        let mut block = String::new();
+        loop {
+            let Some(line) = self.read_line("  ...> ")? else {
+                return Ok(None);
+            };
+
+            if line.trim() == ":}" {
+                return Ok(Some(block));
+            }
+
+            block += &line;
+            block.push('\n');
+        }
+    }
+
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        print!("{prompt}");
+        io::stdout().flush()?;
+
+        self.line.clear();
+        if io::stdin().lock().read_line(&mut self.line)? == 0 {
+            println!();
+            return Ok(None);
+        }
+
+        Ok(Some(self.line.trim_end_matches(['\n', '\r']).to_string()))
+    }
+}
@@ -0,0 +1,182 @@
+//! Source code of all files, that are compiled together.
+//!
+//! The AST borrows from the source code, all its spans are slices of it.
+//! So reading modules happens in two phases:
+//! First all files are loaded into the [`SourceMap`], then they are parsed.
+//! The modules, and the [`crate::compilation::CompilerContext`] built from them,
+//! can't outlive the source map. Once they are dropped, so is the source code,
+//! e.g. when the REPL reloads its files.
+//!
+//! Positions, that need to be kept independently of the AST, are stored as [`Span`].
+
+use std::{fs, io, path::Path};
+
+use crate::diagnostics::Location;
+
+/// Index of a file inside the [`SourceMap`]
+pub type FileId = u32;
+
+/// Position of a piece of source code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub file: FileId,
+    /// in bytes, from the start of the file
+    pub offset: u32,
+    /// in bytes
+    pub len: u32,
+}
+
+impl Span {
+    /// Span of a slice of the content of a file, e.g. an identifier inside the AST.
+    /// Returns None, if the slice isn't part of the content.
+    pub fn within(file: FileId, content: &str, slice: &str) -> Option<Self> {
+        let offset = offset_within(content, slice)?;
+
+        Some(Span {
+            file,
+            offset: offset as u32,
+            len: slice.len() as u32,
+        })
+    }
+}
+
+/// Offset of a slice of the content in bytes.
+/// Returns None, if the slice isn't part of the content.
+pub fn offset_within(content: &str, slice: &str) -> Option<usize> {
+    let offset = (slice.as_ptr() as usize).checked_sub(content.as_ptr() as usize)?;
+    if offset + slice.len() > content.len() {
+        return None;
+    }
+
+    Some(offset)
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub path: String,
+    pub content: String,
+    /// Index of the project inside the [`crate::project::ProjectInfo`], the file belongs to.
+    /// None for code, that isn't part of any project, e.g. typed into the REPL.
+    pub project: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap::default()
+    }
+
+    /// Adds the source code of a file.
+    /// A leading shebang line is blanked, see [`blank_shebang`].
+    pub fn add(&mut self, path: String, mut content: String, project: Option<usize>) -> FileId {
+        blank_shebang(&mut content);
+        content.shrink_to_fit();
+
+        self.files.push(SourceFile {
+            path,
+            content,
+            project,
+        });
+
+        (self.files.len() - 1) as FileId
+    }
+
+    /// Reads a file from disk and adds it.
+    pub fn read(&mut self, path: &Path, project: Option<usize>) -> io::Result<FileId> {
+        let content = fs::read_to_string(path)?;
+        let path = path.to_str().expect("path to be utf-8").to_string();
+
+        Ok(self.add(path, content, project))
+    }
+
+    pub fn get(&self, file: FileId) -> &SourceFile {
+        &self.files[file as usize]
+    }
+
+    /// All files in the order they have been added
+    pub fn iter(&self) -> impl Iterator<Item = (FileId, &SourceFile)> {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(id, file)| (id as FileId, file))
+    }
+
+    pub fn text(&self, span: Span) -> &str {
+        let start = span.offset as usize;
+        &self.get(span.file).content[start..start + span.len as usize]
+    }
+
+    /// Location for displaying the span along with its line of source code.
+    pub fn locate(&self, span: Span) -> Option<Location> {
+        let file = self.get(span.file);
+        Location::from_span(&file.path, &file.content, span)
+    }
+}
+
+/// Scripts may start with a shebang line, e.g. `#!/usr/bin/env -S solar run`.
+/// It is replaced by whitespace, so that the positions of all following code stay the same.
+fn blank_shebang(source_code: &mut String) {
+    if !source_code.starts_with("#!") {
+        return;
+    }
+
+    let end = source_code.find('\n').unwrap_or(source_code.len());
+    source_code.replace_range(..end, &" ".repeat(end));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset_of_slices() {
+        let content = "fun main() = 42";
+
+        assert_eq!(offset_within(content, &content[4..8]), Some(4));
+        assert_eq!(offset_within(content, &content[..0]), Some(0));
+        assert_eq!(offset_within(content, &content[15..]), Some(15));
+    }
+
+    #[test]
+    fn rejects_foreign_slices() {
+        let content = "fun main() = 42";
+        let other = String::from(content);
+
+        assert_eq!(offset_within(content, &other), None);
+        assert_eq!(offset_within(&content[4..], content), None);
+    }
+
+    #[test]
+    fn blanks_shebang_line() {
+        let mut sources = SourceMap::new();
+        let script = "#!/usr/bin/env -S solar run\nfun main() = 42";
+        sources.add("main.sol".to_string(), script.to_string(), None);
+
+        // positions of the following code stay the same
+        let (_, file) = sources.iter().next().unwrap();
+        let blank = " ".repeat("#!/usr/bin/env -S solar run".len());
+        assert_eq!(file.content, format!("{blank}\nfun main() = 42"));
+    }
+
+    #[test]
+    fn spans_refer_to_the_text_of_their_file() {
+        let mut sources = SourceMap::new();
+        sources.add("a.sol".to_string(), "fun a() = 1".to_string(), None);
+        let b = sources.add("b.sol".to_string(), "fun b() = 2".to_string(), None);
+
+        let content = &sources.get(b).content;
+        let span = Span::within(b, content, &content[4..5]).unwrap();
+        assert_eq!((span.file, span.offset, span.len), (b, 4, 1));
+        assert_eq!(sources.text(span), "b");
+
+        let location = sources.locate(span).unwrap();
+        assert_eq!(
+            (location.file.as_str(), location.line, location.column),
+            ("b.sol", 1, 5)
+        );
+    }
+}
@@ -2,11 +2,11 @@ use hotel::HotelMap;
 use solar_parser::ast::body::BodyItem;
 
 use crate::{
-    id::{IdItem, SSID},
+    id::{IdItem, TypeId, SSID},
     project::GlobalModules,
 };
 
-use super::Type;
+use super::{Type, REFERENCE_SIZE};
 
 #[derive(Default, Debug)]
 pub struct BuildinTypeId {
@@ -28,6 +28,45 @@ pub struct BuildinTypeId {
     pub string: u8,
 }
 
+impl BuildinTypeId {
+    /// Returns the id of the buildin type with the given name
+    pub fn by_name(&self, name: &str) -> Option<u8> {
+        let id = match name {
+            "Bool" => self.bool,
+            "Int8" => self.int8,
+            "Int16" => self.int16,
+            "Int32" => self.int32,
+            "Int" => self.int,
+            "Uint8" => self.uint8,
+            "Uint16" => self.uint16,
+            "Uint32" => self.uint32,
+            "Uint" => self.uint,
+            "Float32" => self.float32,
+            "Float" => self.float,
+            "String" => self.string,
+            _ => return None,
+        };
+
+        Some(id)
+    }
+
+    /// Whether the type is one of the buildin integer types
+    pub fn is_int(&self, ty: TypeId) -> bool {
+        [
+            self.int8,
+            self.int16,
+            self.int32,
+            self.int,
+            self.uint8,
+            self.uint16,
+            self.uint32,
+            self.uint,
+        ]
+        .iter()
+        .any(|id| *id as TypeId == ty)
+    }
+}
+
 // Only the stdlibary is allowed to declare buildin types!
 pub fn link_buildin_types(modules: &GlobalModules) -> (HotelMap<SSID, Type>, BuildinTypeId) {
     let mut tys = HotelMap::new();
@@ -43,21 +82,17 @@ pub fn link_buildin_types(modules: &GlobalModules) -> (HotelMap<SSID, Type>, Bui
         for (fid, f) in std.files.iter().enumerate() {
             for (iid, item) in f.ast.items.iter().enumerate() {
                 if let BodyItem::BuildinTypeDecl(item) = item {
-                    assert!(
-                        item.generic_symbols.is_none(),
-                        "can't construct static type from buildin with generics"
-                    );
+                    // Generic buildin types get instantiated on demand,
+                    // once their type arguments are known.
+                    if item.generic_symbols.is_some() {
+                        continue;
+                    }
 
                     let ssid: SSID = (
                         (module.to_vec(), fid as u16, IdItem::Type(iid as u16)),
                         Vec::new(),
                     );
-                    let ty = Type {
-                        info_name: item.name.value.to_string(),
-                        module: module.to_vec(),
-                        field_layout: Vec::new(),
-                        size_in_bytes: 0,
-                    };
+                    let ty = Type::buildin(item.name.value, &ssid, buildin_size(item.name.value));
 
                     let id = tys.insert(ssid, ty) as u8;
 
@@ -83,3 +118,17 @@ pub fn link_buildin_types(modules: &GlobalModules) -> (HotelMap<SSID, Type>, Bui
 
     (tys, ids)
 }
+
+/// Size of buildin types in bytes.
+/// Strings are referenced by pointer and length.
+pub fn buildin_size(name: &str) -> u32 {
+    match name {
+        "Bool" | "Int8" | "Uint8" => 1,
+        "Int16" | "Uint16" => 2,
+        "Int32" | "Uint32" | "Float32" => 4,
+        "Int" | "Uint" | "Float" => 8,
+        "String" => 16,
+        // e.g. generic containers
+        _ => REFERENCE_SIZE,
+    }
+}
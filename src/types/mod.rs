@@ -1,19 +1,121 @@
 pub mod buildin;
 
-use crate::id::{IdModule, TypeId};
+use serde::{Deserialize, Serialize};
+
+use crate::id::{IdModule, TypeId, SSID};
+
+/// Size of a reference to a value, e.g. for recursive types.
+pub const REFERENCE_SIZE: u32 = 8;
+
+/// Size of the tag, that precedes the payload of enum values.
+pub const TAG_SIZE: u32 = 2;
 
 /// Represents a concrete, static type
 /// and the information needed to construct it.
-#[derive(Debug, Clone, Hash, PartialEq, PartialOrd, Eq, Ord)]
+#[derive(Debug, Clone, Hash, PartialEq, PartialOrd, Eq, Ord, Serialize, Deserialize)]
 pub struct Type {
     /// Module used for looking up functions associated with this type
     pub info_name: String,
-    module: IdModule,
+    /// Declaration and type arguments this type was instantiated from.
+    ssid: SSID,
     size_in_bytes: u32,
     field_layout: Vec<(String, u32, TypeId)>,
+    /// Name and payload of each variant, if this is an enum.
+    /// The payload is stored right after the tag.
+    variants: Vec<(String, Option<TypeId>)>,
 }
 
 impl Type {
+    /// Placeholder for a type, whose fields are not known yet.
+    /// Needed, so that types can refer to themselves.
+    pub fn reserved(info_name: impl Into<String>, ssid: &SSID) -> Self {
+        Type::buildin(info_name, ssid, 0)
+    }
+
+    /// Type without fields, that is known to the compiler.
+    pub fn buildin(info_name: impl Into<String>, ssid: &SSID, size_in_bytes: u32) -> Self {
+        Type {
+            info_name: info_name.into(),
+            ssid: ssid.clone(),
+            size_in_bytes,
+            field_layout: Vec::new(),
+            variants: Vec::new(),
+        }
+    }
+
+    /// Creates a struct type from (name, type, size) of each field.
+    /// The fields are laid out in order of their declaration.
+    pub fn new_struct(
+        info_name: impl Into<String>,
+        ssid: &SSID,
+        fields: Vec<(String, TypeId, u32)>,
+    ) -> Self {
+        let mut offset = 0;
+        let mut field_layout = Vec::with_capacity(fields.len());
+
+        for (name, ty, size) in fields {
+            field_layout.push((name, offset, ty));
+            offset += size;
+        }
+
+        Type {
+            info_name: info_name.into(),
+            ssid: ssid.clone(),
+            size_in_bytes: offset,
+            field_layout,
+            variants: Vec::new(),
+        }
+    }
+
+    /// Creates an enum type from (name, payload type, payload size) of each variant.
+    /// Values consist of a tag, followed by the largest payload.
+    pub fn new_enum(
+        info_name: impl Into<String>,
+        ssid: &SSID,
+        variants: Vec<(String, Option<TypeId>, u32)>,
+    ) -> Self {
+        let payload_size = variants.iter().map(|(_, _, size)| *size).max().unwrap_or(0);
+
+        Type {
+            info_name: info_name.into(),
+            ssid: ssid.clone(),
+            size_in_bytes: TAG_SIZE + payload_size,
+            field_layout: Vec::new(),
+            variants: variants
+                .into_iter()
+                .map(|(name, payload, _)| (name, payload))
+                .collect(),
+        }
+    }
+
+    /// Module used for looking up functions associated with this type
+    pub fn module(&self) -> &IdModule {
+        &self.ssid.0.0
+    }
+
+    /// Declaration and type arguments this type was instantiated from.
+    pub fn ssid(&self) -> &SSID {
+        &self.ssid
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size_in_bytes
+    }
+
+    pub fn is_enum(&self) -> bool {
+        !self.variants.is_empty()
+    }
+
+    /// Name and payload type of all variants
+    pub fn variants(&self) -> &[(String, Option<TypeId>)] {
+        &self.variants
+    }
+
+    /// Name, offset and TypeId of all fields
+    pub fn fields(&self) -> &[(String, u32, TypeId)] {
+        &self.field_layout
+    }
+
     /// returns the offset, length and TypeId of the given field
     pub fn get_field(&self, name: &str) -> Result<(u32, u32, TypeId), ()> {
         for (index, (n, offset, id)) in self.field_layout.iter().enumerate() {
@@ -21,10 +123,10 @@ impl Type {
                 continue;
             }
 
-            let next = if index == self.field_layout.len() {
+            let next = if index + 1 == self.field_layout.len() {
                 self.size_in_bytes
             } else {
-                self.field_layout[index].1
+                self.field_layout[index + 1].1
             };
 
             let len = next - offset;
@@ -26,6 +26,22 @@ pub(crate) fn normalize_path(path: &IdentifierPath) -> Vec<String> {
     path.value.iter().map(|i| i.value.to_string()).collect()
 }
 
+/// Path of the type referenced by a type annotation, e.g. `n: String`
+pub(crate) fn type_path(ty: &ast::Type) -> Vec<String> {
+    normalize_path(&ty.path)
+}
+
+/// Type arguments of a type annotation, e.g. the `Int` in `Maybe Int`
+pub(crate) fn type_args(ty: &ast::Type) -> &[ast::Type] {
+    &ty.generics
+}
+
+/// Lowercase names in type annotations of functions are type variables,
+/// e.g. `a` in `fun unwrap(w: Wrapper a) -> a`
+pub(crate) fn is_type_variable(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_lowercase())
+}
+
 pub(crate) fn eval_int(
     int: &ast::expr::literal::Int,
     types: &BuildinTypeId,
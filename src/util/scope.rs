@@ -39,6 +39,18 @@ impl Scope {
         index
     }
 
+    /// Changes the type of the most recent value with the given name, keeping its index.
+    /// The value is pushed, if there is none with that name yet.
+    pub fn set(&mut self, name: &str, ty: TypeId) -> u16 {
+        match self.values.iter_mut().rfind(|(n, _, _)| n == name) {
+            Some((_, old, index)) => {
+                *old = ty;
+                *index
+            }
+            None => self.push(name, ty),
+        }
+    }
+
     /// Pops the most recent value out of the scope.
     /// Popping of an empty scope is considered a programming error
     /// and results in a panic.
@@ -1,15 +1,25 @@
 use std::fmt::{self, Debug};
 
+use serde::{Deserialize, Serialize};
+
 use crate::id::TypeId;
 
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Value {
     Void,
     Bool(bool),
     Int(Int),
     Float(f64),
     String(String),
+    /// Instance of a user defined struct
+    Struct { ty: TypeId, fields: Vec<Value> },
+    /// Value of a user defined enum
+    Enum {
+        ty: TypeId,
+        variant: u16,
+        payload: Option<Box<Value>>,
+    },
 }
 
 impl Value {
@@ -24,6 +34,8 @@ impl Value {
             Value::Int(_) => "Int",
             Value::Float(_) => "Float",
             Value::String(_) => "String",
+            Value::Struct { .. } => "Struct",
+            Value::Enum { .. } => "Enum",
         }
     }
 }
@@ -44,11 +56,81 @@ impl fmt::Display for Value {
             Value::Int(i) => write!(f, "{i}"),
             Value::Float(i) => write!(f, "{i}"),
             Value::String(i) => write!(f, "{i}"),
+            Value::Struct { fields, .. } => {
+                write!(f, "{{")?;
+                for (i, field) in fields.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{field}")?;
+                }
+                write!(f, "}}")
+            }
+            Value::Enum {
+                variant, payload, ..
+            } => match payload {
+                Some(payload) => write!(f, "#{variant}({payload})"),
+                None => write!(f, "#{variant}"),
+            },
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Displays a value, naming the variants of enums.
+/// The names are looked up by the type and the index of the variant.
+/// Variants without a name are displayed by their index, e.g. `#0`.
+pub struct Named<'v, F> {
+    value: &'v Value,
+    variant_name: &'v F,
+}
+
+impl Value {
+    pub fn named<'v, F>(&'v self, variant_name: &'v F) -> Named<'v, F>
+    where
+        F: Fn(TypeId, u16) -> Option<String>,
+    {
+        Named {
+            value: self,
+            variant_name,
+        }
+    }
+}
+
+impl<F> fmt::Display for Named<'_, F>
+where
+    F: Fn(TypeId, u16) -> Option<String>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value {
+            Value::Struct { fields, .. } => {
+                write!(f, "{{")?;
+                for (i, field) in fields.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", field.named(self.variant_name))?;
+                }
+                write!(f, "}}")
+            }
+            Value::Enum {
+                ty,
+                variant,
+                payload,
+            } => {
+                let name =
+                    (self.variant_name)(*ty, *variant).unwrap_or_else(|| format!("#{variant}"));
+
+                match payload {
+                    Some(payload) => write!(f, "{name}({})", payload.named(self.variant_name)),
+                    None => write!(f, "{name}"),
+                }
+            }
+            value => write!(f, "{value}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum Int {
     Int64(i64),
     Int32(i32),
@@ -60,6 +142,39 @@ pub enum Int {
     Uint8(u8),
 }
 
+impl Int {
+    pub fn as_i128(self) -> i128 {
+        use Int::*;
+        match self {
+            Int64(v) => v as i128,
+            Int32(v) => v as i128,
+            Int16(v) => v as i128,
+            Int8(v) => v as i128,
+            Uint64(v) => v as i128,
+            Uint32(v) => v as i128,
+            Uint16(v) => v as i128,
+            Uint8(v) => v as i128,
+        }
+    }
+
+    /// Applies an operation to two integers.
+    /// The result has the type of `self`, overflows wrap around.
+    pub fn map2(self, other: Int, f: impl Fn(i128, i128) -> i128) -> Int {
+        use Int::*;
+        let v = f(self.as_i128(), other.as_i128());
+        match self {
+            Int64(_) => Int64(v as i64),
+            Int32(_) => Int32(v as i32),
+            Int16(_) => Int16(v as i16),
+            Int8(_) => Int8(v as i8),
+            Uint64(_) => Uint64(v as u64),
+            Uint32(_) => Uint32(v as u32),
+            Uint16(_) => Uint16(v as u16),
+            Uint8(_) => Uint8(v as u8),
+        }
+    }
+}
+
 impl fmt::Display for Int {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use Int::*;
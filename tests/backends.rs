@@ -0,0 +1,77 @@
+//! The bytecode vm has to behave exactly like the tree walking interpreter.
+//! Each sample is run on both backends, comparing what they print.
+//! `solar bench` runs it once more, comparing the values returned by the entry function.
+//! The samples use the std library shipped in `solarpath/`.
+
+use std::process::{Command, Output};
+
+fn solar(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_solar"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env(
+            "SOLAR_PATH",
+            concat!(env!("CARGO_MANIFEST_DIR"), "/solarpath/"),
+        )
+        .output()
+        .expect("run solar")
+}
+
+fn assert_backends_agree(sample: &str) {
+    let tree = solar(&["run", sample, "--backend", "tree"]);
+    assert!(
+        tree.status.success(),
+        "{sample} failed: {}",
+        String::from_utf8_lossy(&tree.stderr)
+    );
+
+    let bytecode = solar(&["run", sample, "--backend", "bytecode", "--no-cache"]);
+    assert_eq!(
+        String::from_utf8_lossy(&tree.stdout),
+        String::from_utf8_lossy(&bytecode.stdout),
+        "{sample} prints different output on the bytecode vm"
+    );
+    assert_eq!(tree.status.code(), bytecode.status.code());
+
+    let bench = solar(&["bench", sample, "--iterations", "1"]);
+    assert!(
+        bench.status.success(),
+        "{sample}: {}",
+        String::from_utf8_lossy(&bench.stderr)
+    );
+}
+
+#[test]
+fn no_deps() {
+    assert_backends_agree("samples/4-no-deps");
+}
+
+#[test]
+fn calling_convention() {
+    assert_backends_agree("samples/2-calling-convention.sol");
+}
+
+#[test]
+fn recursion() {
+    assert_backends_agree("samples/5-recursion");
+}
+
+#[test]
+fn structs() {
+    assert_backends_agree("samples/6-structs");
+}
+
+#[test]
+fn enums() {
+    assert_backends_agree("samples/7-enums");
+}
+
+#[test]
+fn generics() {
+    assert_backends_agree("samples/8-generics");
+}
+
+#[test]
+fn globals() {
+    assert_backends_agree("samples/9-globals");
+}